@@ -0,0 +1,336 @@
+use crate::regex::decode_char;
+
+// An Aho-Corasick automaton finds any number of literal patterns in a single pass over the text.
+// The patterns are put into a trie, and every node gets a failure link to the longest suffix of
+// its path that is also a path in the trie, so the scan never has to go back in the text.
+#[derive(Debug, Clone)]
+pub struct AhoCorasick {
+    nodes: Vec<Node>,
+    // Length of each pattern in chars
+    pattern_lens: Vec<usize>,
+    max_pattern_len: usize,
+    ignore_case: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    // Sorted by char so transitions can be binary searched, which keeps big tries small in memory
+    transitions: Vec<(char, usize)>,
+    fail: usize,
+    // Number of chars from the root to this node
+    depth: usize,
+    // The patterns that end at this node, including the ones reached through failure links
+    outputs: Vec<usize>,
+}
+
+impl Node {
+    fn new(depth: usize) -> Self {
+        Self {
+            transitions: Vec::new(),
+            fail: ROOT,
+            depth,
+            outputs: Vec::new(),
+        }
+    }
+
+    fn transition(&self, c: char) -> Option<usize> {
+        self.transitions
+            .binary_search_by_key(&c, |&(key, _)| key)
+            .ok()
+            .map(|index| self.transitions[index].1)
+    }
+}
+
+const ROOT: usize = 0;
+
+impl AhoCorasick {
+    pub fn new<S: AsRef<str>>(patterns: &[S], ignore_case: bool) -> Self {
+        let mut automaton = Self {
+            nodes: vec![Node::new(0)],
+            pattern_lens: Vec::new(),
+            max_pattern_len: 0,
+            ignore_case,
+        };
+
+        for (pattern_id, pattern) in patterns.iter().enumerate() {
+            automaton.insert(pattern_id, pattern.as_ref());
+        }
+        automaton.build_failure_links();
+
+        automaton
+    }
+
+    fn insert(&mut self, pattern_id: usize, pattern: &str) {
+        let mut node = ROOT;
+        let mut len = 0;
+
+        for c in pattern.chars() {
+            let c = self.fold(c);
+            len += 1;
+
+            node = match self.nodes[node].transition(c) {
+                Some(next) => next,
+                None => {
+                    let next = self.nodes.len();
+                    self.nodes.push(Node::new(len));
+
+                    let transitions = &mut self.nodes[node].transitions;
+                    let index = transitions.partition_point(|&(key, _)| key < c);
+                    transitions.insert(index, (c, next));
+
+                    next
+                }
+            };
+        }
+
+        self.nodes[node].outputs.push(pattern_id);
+        self.pattern_lens.push(len);
+        self.max_pattern_len = self.max_pattern_len.max(len);
+    }
+
+    // Failure links are set breadth first, since a node's link always points to a shallower node
+    fn build_failure_links(&mut self) {
+        let mut queue = std::collections::VecDeque::new();
+
+        for &(_, child) in &self.nodes[ROOT].transitions {
+            queue.push_back(child);
+        }
+
+        while let Some(node) = queue.pop_front() {
+            let transitions = self.nodes[node].transitions.clone();
+
+            for (c, child) in transitions {
+                // Follow the parent's failure links until a node with a transition on c is found
+                let mut fail = self.nodes[node].fail;
+                let child_fail = loop {
+                    if let Some(next) = self.nodes[fail].transition(c) {
+                        break next;
+                    }
+                    if fail == ROOT {
+                        break ROOT;
+                    }
+                    fail = self.nodes[fail].fail;
+                };
+
+                self.nodes[child].fail = child_fail;
+
+                // A pattern that ends at the failure node also ends here
+                let inherited = self.nodes[child_fail].outputs.clone();
+                self.nodes[child].outputs.extend(inherited);
+
+                queue.push_back(child);
+            }
+        }
+    }
+
+    fn fold(&self, c: char) -> char {
+        if self.ignore_case { fold_case(c) } else { c }
+    }
+
+    fn next_node(&self, mut node: usize, c: char) -> usize {
+        loop {
+            if let Some(next) = self.nodes[node].transition(c) {
+                return next;
+            }
+            if node == ROOT {
+                return ROOT;
+            }
+            node = self.nodes[node].fail;
+        }
+    }
+
+    pub fn is_match(&self, text: &[u8]) -> bool {
+        if !self.nodes[ROOT].outputs.is_empty() {
+            return true; // An empty pattern matches everywhere
+        }
+
+        let mut node = ROOT;
+        let mut at = 0;
+        while let Some((c, len)) = decode_char(text, at) {
+            node = self.next_node(node, self.fold(c));
+            if !self.nodes[node].outputs.is_empty() {
+                return true;
+            }
+            at += len;
+        }
+
+        false
+    }
+
+    // Returns the byte range of the leftmost match starting at or after the byte offset start.
+    // If several patterns match at the same place, the longest one wins.
+    pub fn find_at(&self, text: &[u8], start: usize) -> Option<(usize, usize)> {
+        self.find_at_where(text, start, |_, _| true)
+    }
+
+    // Like find_at, but only the matches that accept lets through (given their byte range) count.
+    // When the longest pattern at a place is not accepted, the shorter ones there are still tried,
+    // like for -w where foo can be a whole word in foo-bar while foo-b is not.
+    pub fn find_at_where(
+        &self,
+        text: &[u8],
+        start: usize,
+        accept: impl Fn(usize, usize) -> bool,
+    ) -> Option<(usize, usize)> {
+        // Byte offsets of the latest chars, so a match's start can be found from its length in chars.
+        // A match never reaches further back than the longest pattern, and the scan stops soon after
+        // the best match is found, so only that many offsets have to be kept, even for a huge text.
+        let mut char_offsets = CharOffsets::new(self.max_pattern_len + 2);
+        let mut best: Option<(usize, usize)> = None; // As char indices
+
+        let mut node = ROOT;
+        // end is the char index the matches end at, and end_offset its byte offset
+        let consider = |best: &mut Option<(usize, usize)>, char_offsets: &CharOffsets, node: usize, end: usize, end_offset: usize| {
+            for &pattern_id in &self.nodes[node].outputs {
+                let match_start = end - self.pattern_lens[pattern_id];
+                let is_better = match *best {
+                    Some((best_start, best_end)) => {
+                        match_start < best_start || (match_start == best_start && end > best_end)
+                    }
+                    None => true,
+                };
+                let start_offset = if match_start == end { end_offset } else { char_offsets.get(match_start) };
+                if is_better && accept(start_offset, end_offset) {
+                    *best = Some((match_start, end));
+                }
+            }
+        };
+
+        consider(&mut best, &char_offsets, node, 0, start);
+
+        // Byte offset right after the last char that was scanned
+        let mut scanned_to = start;
+
+        while let Some((c, len)) = decode_char(text, scanned_to) {
+            char_offsets.push(scanned_to);
+            scanned_to += len;
+            let end = char_offsets.count;
+
+            node = self.next_node(node, self.fold(c));
+            consider(&mut best, &char_offsets, node, end, scanned_to);
+
+            // Every match still in progress started at end - depth, so once that is past the best
+            // match's start, nothing further to the right can beat it
+            if let Some((best_start, _)) = best
+                && end - self.nodes[node].depth > best_start
+            {
+                break;
+            }
+        }
+
+        char_offsets.push(scanned_to);
+        best.map(|(match_start, match_end)| (char_offsets.get(match_start), char_offsets.get(match_end)))
+    }
+}
+
+// A ring of the byte offsets of the latest chars, looked up by char index
+struct CharOffsets {
+    offsets: Vec<usize>,
+    // How many offsets were pushed in total, which is the char index of the next one
+    count: usize,
+}
+
+impl CharOffsets {
+    fn new(capacity: usize) -> Self {
+        Self {
+            offsets: vec![0; capacity],
+            count: 0,
+        }
+    }
+
+    fn push(&mut self, offset: usize) {
+        let capacity = self.offsets.len();
+        self.offsets[self.count % capacity] = offset;
+        self.count += 1;
+    }
+
+    fn get(&self, char_index: usize) -> usize {
+        self.offsets[char_index % self.offsets.len()]
+    }
+}
+
+// Folds a char to its lowercase form, as long as that is a single char, so the byte offsets
+// in the text always line up with the chars that were compared
+pub fn fold_case(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(lower), None) => lower,
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aho_corasick_finds_leftmost_match() {
+        let automaton = AhoCorasick::new(&["she", "he", "hers"], false);
+
+        assert_eq!(Some((1, 4)), automaton.find_at(b"ushers", 0))
+    }
+
+    #[test]
+    fn aho_corasick_prefers_longest_match_at_same_start() {
+        let automaton = AhoCorasick::new(&["he", "hers"], false);
+
+        assert_eq!(Some((2, 6)), automaton.find_at(b"ushers", 0))
+    }
+
+    #[test]
+    fn aho_corasick_uses_failure_links() {
+        let automaton = AhoCorasick::new(&["abcd", "bce"], false);
+
+        assert_eq!(Some((2, 5)), automaton.find_at(b"abbce", 0));
+        assert_eq!(None, automaton.find_at(b"abcxd", 0));
+    }
+
+    #[test]
+    fn aho_corasick_starts_searching_at_offset() {
+        let automaton = AhoCorasick::new(&["ab"], false);
+
+        assert_eq!(Some((3, 5)), automaton.find_at(b"ab ab", 1));
+    }
+
+    #[test]
+    fn aho_corasick_ignores_case_when_built_with_it() {
+        let automaton = AhoCorasick::new(&["ÅSA", "timeout"], true);
+
+        assert!(automaton.is_match(b"connection TimeOut"));
+        assert_eq!(Some((4, 8)), automaton.find_at("hej åsa".as_bytes(), 0));
+    }
+
+    #[test]
+    fn aho_corasick_handles_many_patterns() {
+        let patterns: Vec<String> = (0..10_000).map(|i| format!("E{i:05}")).collect();
+        let automaton = AhoCorasick::new(&patterns, false);
+
+        assert!(automaton.is_match(b"failed with E09999"));
+        assert!(!automaton.is_match(b"failed with E1000"));
+        assert_eq!(Some((12, 18)), automaton.find_at(b"failed with E04242!", 0));
+    }
+
+    #[test]
+    fn aho_corasick_without_patterns_matches_nothing() {
+        let automaton = AhoCorasick::new::<&str>(&[], false);
+
+        assert!(!automaton.is_match(b"anything"));
+        assert_eq!(None, automaton.find_at(b"anything", 0));
+    }
+
+    #[test]
+    fn aho_corasick_finds_matches_among_invalid_utf8() {
+        let automaton = AhoCorasick::new(&["error", "ÉCHEC"], true);
+
+        assert_eq!(Some((2, 7)), automaton.find_at(b"\xff\xfeERROR\xc3", 0));
+        assert_eq!(Some((3, 9)), automaton.find_at(b"\x01\xe9 \xc3\xa9chec", 0));
+    }
+
+    #[test]
+    fn aho_corasick_finds_match_far_into_text() {
+        let automaton = AhoCorasick::new(&["needle", "dle"], false);
+        let text = format!("{}needle", "hay ".repeat(100_000));
+
+        assert_eq!(Some((400_000, 400_006)), automaton.find_at(text.as_bytes(), 0));
+    }
+}
@@ -0,0 +1,287 @@
+// A small command line parser in the style of getopt_long. It knows which options exist and which
+// of them take a value, and turns the raw args into options and positional args:
+//
+// - Short flags can be clustered, so -in is the same as -i -n
+// - A short option's value is the rest of the cluster or the next arg, so -A1 and -A 1 both work
+// - Long options are written --name, with their value as --name=value or as the next arg
+// - Everything after -- is positional, even if it starts with '-'
+// - A lone - is positional, since it names standard input
+// - Options and positional args can come in any order
+
+use crate::error::MinigrepError;
+
+pub struct OptionSpec {
+    pub short: Option<char>,
+    pub long: &'static str,
+    pub takes_value: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Arg {
+    // An option named by its long name, no matter how it was written, then the flag as it was written
+    // (like -B or --before-context, for error messages), and its value if it takes one
+    Option(&'static str, String, Option<String>),
+    Positional(String),
+}
+
+pub fn parse(args: &[String], specs: &'static [OptionSpec]) -> Result<Vec<Arg>, MinigrepError> {
+    let mut parsed: Vec<Arg> = Vec::new();
+    let mut args_iter = args.iter();
+
+    while let Some(arg) = args_iter.next() {
+        if arg == "--" {
+            parsed.extend(args_iter.by_ref().map(|arg| Arg::Positional(arg.clone())));
+            break;
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, attached_value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(String::from(value))),
+                None => (long, None),
+            };
+
+            let spec = specs
+                .iter()
+                .find(|spec| spec.long == name)
+                .ok_or_else(|| unknown_flag(&format!("--{name}"), name, specs))?;
+
+            let value = match (spec.takes_value, attached_value) {
+                (true, Some(value)) => Some(value),
+                (true, None) => Some(next_value(&mut args_iter, &format!("--{name}"))?),
+                (false, Some(_)) => {
+                    return Err(MinigrepError::BadFlag {
+                        flag: format!("--{name}"),
+                        message: String::from("does not take a value"),
+                    });
+                }
+                (false, None) => None,
+            };
+
+            parsed.push(Arg::Option(spec.long, format!("--{name}"), value));
+            continue;
+        }
+
+        match arg.strip_prefix('-') {
+            Some(cluster) if !cluster.is_empty() => {
+                parse_short_cluster(cluster, &mut args_iter, specs, &mut parsed)?
+            }
+            _ => parsed.push(Arg::Positional(arg.clone())),
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn parse_short_cluster<'a>(
+    cluster: &str,
+    args_iter: &mut impl Iterator<Item = &'a String>,
+    specs: &'static [OptionSpec],
+    parsed: &mut Vec<Arg>,
+) -> Result<(), MinigrepError> {
+    for (offset, short) in cluster.char_indices() {
+        // Something like -ignore-case is most likely a long option with a dash missing,
+        // so the suggestion is based on the whole cluster
+        let spec = specs
+            .iter()
+            .find(|spec| spec.short == Some(short))
+            .ok_or_else(|| unknown_flag(&format!("-{short}"), cluster, specs))?;
+
+        let flag = format!("-{short}");
+        if !spec.takes_value {
+            parsed.push(Arg::Option(spec.long, flag, None));
+            continue;
+        }
+
+        // An option that takes a value ends the cluster
+        let rest = &cluster[offset + short.len_utf8()..];
+        let value = if rest.is_empty() {
+            next_value(args_iter, &flag)?
+        } else {
+            String::from(rest)
+        };
+
+        parsed.push(Arg::Option(spec.long, flag, Some(value)));
+        break;
+    }
+
+    Ok(())
+}
+
+fn next_value<'a>(
+    args_iter: &mut impl Iterator<Item = &'a String>,
+    flag: &str,
+) -> Result<String, MinigrepError> {
+    match args_iter.next() {
+        Some(value) => Ok(value.clone()),
+        None => Err(MinigrepError::BadFlag {
+            flag: String::from(flag),
+            message: String::from("requires a value"),
+        }),
+    }
+}
+
+fn unknown_flag(flag: &str, name: &str, specs: &'static [OptionSpec]) -> MinigrepError {
+    MinigrepError::UnknownFlag {
+        flag: String::from(flag),
+        suggestion: suggest(name, specs),
+    }
+}
+
+// Finds the long option that is closest to what was written, so a typo like --ignore-cas or
+// a cut short option like --ignore can be pointed at the option that was meant
+pub fn suggest(name: &str, specs: &'static [OptionSpec]) -> Option<&'static str> {
+    // A single char is a short flag, and comparing it to the long names would suggest nonsense
+    if name.chars().count() < 2 {
+        return None;
+    }
+
+    specs
+        .iter()
+        .map(|spec| {
+            let distance = if spec.long.starts_with(name) {
+                0
+            } else {
+                edit_distance(name, spec.long)
+            };
+            (distance, spec.long)
+        })
+        .filter(|&(distance, long)| distance <= 2.max(long.len() / 3))
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, long)| long)
+}
+
+// The Levenshtein distance: how many chars have to be inserted, removed or replaced
+// to turn one string into the other
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // The distances from the part of a seen so far to every prefix of b
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, &b_char) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(a_char != b_char);
+            let remove = previous[j + 1] + 1;
+            let insert = current[j] + 1;
+            current.push(replace.min(remove).min(insert));
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPECS: &[OptionSpec] = &[
+        OptionSpec { short: Some('i'), long: "ignore-case", takes_value: false },
+        OptionSpec { short: Some('n'), long: "line-number", takes_value: false },
+        OptionSpec { short: Some('m'), long: "max-count", takes_value: true },
+        OptionSpec { short: None, long: "json", takes_value: false },
+    ];
+
+    fn parse_args(args: &[&str]) -> Result<Vec<Arg>, MinigrepError> {
+        let args: Vec<String> = args.iter().map(|arg| String::from(*arg)).collect();
+        parse(&args, SPECS)
+    }
+
+    fn option(name: &'static str, flag: &str, value: Option<&str>) -> Arg {
+        Arg::Option(name, String::from(flag), value.map(String::from))
+    }
+
+    #[test]
+    fn parse_splits_clustered_short_flags() {
+        assert_eq!(
+            vec![option("ignore-case", "-i", None), option("line-number", "-n", None)],
+            parse_args(&["-in"]).unwrap()
+        )
+    }
+
+    #[test]
+    fn parse_takes_short_option_value_from_cluster_or_next_arg() {
+        assert_eq!(
+            vec![
+                option("ignore-case", "-i", None),
+                option("max-count", "-m", Some("5")),
+                option("max-count", "-m", Some("7")),
+            ],
+            parse_args(&["-im5", "-m", "7"]).unwrap()
+        )
+    }
+
+    #[test]
+    fn parse_takes_long_option_value_after_equals_or_next_arg() {
+        assert_eq!(
+            vec![
+                option("max-count", "--max-count", Some("5")),
+                option("max-count", "--max-count", Some("-1")),
+                option("json", "--json", None),
+            ],
+            parse_args(&["--max-count=5", "--max-count", "-1", "--json"]).unwrap()
+        )
+    }
+
+    #[test]
+    fn parse_keeps_positional_args_in_any_order() {
+        assert_eq!(
+            vec![
+                Arg::Positional(String::from("foo")),
+                option("ignore-case", "-i", None),
+                Arg::Positional(String::from("-")),
+                Arg::Positional(String::from("file")),
+            ],
+            parse_args(&["foo", "-i", "-", "file"]).unwrap()
+        )
+    }
+
+    #[test]
+    fn parse_treats_everything_after_double_dash_as_positional() {
+        assert_eq!(
+            vec![
+                option("line-number", "-n", None),
+                Arg::Positional(String::from("-v")),
+                Arg::Positional(String::from("--json")),
+            ],
+            parse_args(&["-n", "--", "-v", "--json"]).unwrap()
+        )
+    }
+
+    #[test]
+    fn parse_fails_on_unknown_or_misused_options() {
+        assert!(parse_args(&["-x"]).is_err());
+        assert!(parse_args(&["--ignore"]).is_err());
+        assert!(parse_args(&["--json=yes"]).is_err());
+        assert!(parse_args(&["-m"]).is_err());
+        assert!(parse_args(&["--max-count"]).is_err());
+    }
+
+    #[test]
+    fn parse_names_missing_value_by_flag_as_written() {
+        let flag = |args: &[&str]| match parse_args(args) {
+            Err(MinigrepError::BadFlag { flag, .. }) => flag,
+            _ => panic!("expected a bad flag"),
+        };
+
+        assert_eq!("-m", flag(&["-im"]));
+        assert_eq!("--max-count", flag(&["--max-count"]));
+    }
+
+    #[test]
+    fn parse_suggests_similar_long_option() {
+        let suggestion = |args: &[&str]| match parse_args(args) {
+            Err(MinigrepError::UnknownFlag { suggestion, .. }) => suggestion,
+            _ => panic!("expected an unknown flag"),
+        };
+
+        assert_eq!(Some("ignore-case"), suggestion(&["--ignore"]));
+        assert_eq!(Some("ignore-case"), suggestion(&["--ignroe-case"]));
+        assert_eq!(Some("ignore-case"), suggestion(&["-ignore-case"]));
+        assert_eq!(Some("json"), suggestion(&["--jsno"]));
+        assert_eq!(None, suggestion(&["--verbose"]));
+        assert_eq!(None, suggestion(&["-x"]));
+    }
+}
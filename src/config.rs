@@ -1,130 +1,511 @@
 use std::env;
+use std::io::{self, Write};
+use std::thread;
+
+use crate::args::{self, Arg, OptionSpec};
+use crate::encoding::Encoding;
+use crate::error::MinigrepError;
+use crate::filter::PathFilter;
+use crate::types::FileTypes;
+
+// Every option minigrep knows, named by the long name that Config::build looks them up by
+const OPTIONS: &[OptionSpec] = &[
+    OptionSpec { short: Some('i'), long: "ignore-case", takes_value: false },
+    OptionSpec { short: Some('n'), long: "line-number", takes_value: false },
+    OptionSpec { short: Some('w'), long: "word-regexp", takes_value: false },
+    OptionSpec { short: Some('v'), long: "invert-match", takes_value: false },
+    OptionSpec { short: Some('E'), long: "extended-regexp", takes_value: false },
+    OptionSpec { short: Some('e'), long: "regexp", takes_value: true },
+    OptionSpec { short: Some('f'), long: "file", takes_value: true },
+    OptionSpec { short: Some('r'), long: "recursive", takes_value: false },
+    OptionSpec { short: Some('A'), long: "after-context", takes_value: true },
+    OptionSpec { short: Some('B'), long: "before-context", takes_value: true },
+    OptionSpec { short: Some('C'), long: "context", takes_value: true },
+    OptionSpec { short: Some('c'), long: "count", takes_value: false },
+    OptionSpec { short: None, long: "count-matches", takes_value: false },
+    OptionSpec { short: None, long: "stats", takes_value: false },
+    OptionSpec { short: Some('o'), long: "only-matching", takes_value: false },
+    OptionSpec { short: None, long: "column", takes_value: false },
+    OptionSpec { short: None, long: "json", takes_value: false },
+    OptionSpec { short: Some('q'), long: "quiet", takes_value: false },
+    OptionSpec { short: Some('s'), long: "no-messages", takes_value: false },
+    OptionSpec { short: Some('l'), long: "files-with-matches", takes_value: false },
+    OptionSpec { short: Some('L'), long: "files-without-match", takes_value: false },
+    OptionSpec { short: Some('0'), long: "null", takes_value: false },
+    OptionSpec { short: Some('m'), long: "max-count", takes_value: true },
+    OptionSpec { short: Some('j'), long: "threads", takes_value: true },
+    OptionSpec { short: None, long: "sort", takes_value: true },
+    OptionSpec { short: None, long: "no-ignore", takes_value: false },
+    OptionSpec { short: None, long: "hidden", takes_value: false },
+    OptionSpec { short: Some('g'), long: "glob", takes_value: true },
+    OptionSpec { short: Some('t'), long: "type", takes_value: true },
+    OptionSpec { short: Some('T'), long: "type-not", takes_value: true },
+    OptionSpec { short: None, long: "type-add", takes_value: true },
+    OptionSpec { short: None, long: "type-list", takes_value: false },
+    OptionSpec { short: None, long: "binary-files", takes_value: true },
+    OptionSpec { short: Some('b'), long: "byte-offset", takes_value: false },
+    OptionSpec { short: None, long: "encoding", takes_value: true },
+    OptionSpec { short: Some('z'), long: "search-zip", takes_value: false },
+    OptionSpec { short: None, long: "help", takes_value: false },
+];
+
+// Looks for --help with the parser, so a --help that is the value of an option (like -e --help)
+// or comes after -- is searched for like any other pattern. Args that do not parse ask for no help,
+// the error from Config::build tells what is wrong with them.
+pub fn is_help_requested(args: &[String]) -> bool {
+    // Skip the first arg since it is the program name
+    args::parse(&args[1..], OPTIONS)
+        .is_ok_and(|parsed| parsed.iter().any(|arg| matches!(arg, Arg::Option("help", _, _))))
+}
 
 pub fn print_help() {
-    println!("minigrep - Search for pattern in a file");
+    println!("minigrep - Search for pattern in files");
     println!();
     println!("Usage:");
-    println!("  minigrep [OPTIONS] <PATTERN> <FILE>");
+    println!("  minigrep [OPTIONS] <PATTERN> [FILE]...");
+    println!("  minigrep [OPTIONS] -e <PATTERN>... [FILE]...");
+    println!("  minigrep [OPTIONS] -f <PATTERN_FILE> [FILE]...");
+    println!();
+    println!("With no FILE, or when FILE is -, standard input is read.");
     println!();
     println!("Options:");
-    println!("  -i           Ignore case");
-    println!("  -n           Show line numbers");
-    println!("  -w           Match whole words only");
-    println!("  -v           Invert match (show non-matching lines)");
-    println!("  --help       Show this help message and exit");
+    println!("  -i, --ignore-case          Ignore case");
+    println!("  -n, --line-number          Show line numbers");
+    println!("  -w, --word-regexp          Match whole words only");
+    println!("  -v, --invert-match         Invert match (show non-matching lines)");
+    println!("  -E, --extended-regexp      Interpret pattern as a regular expression");
+    println!("  -e, --regexp PATTERN       Search for PATTERN (can be given multiple times)");
+    println!("  -f, --file FILE            Read patterns from FILE, one per line");
+    println!("                             (a blank line matches every line)");
+    println!("  -r, --recursive            Search directories recursively, skipping hidden and ignored files");
+    println!("      --no-ignore            With -r, also search files that .gitignore and .ignore files ignore");
+    println!("      --hidden               With -r, also search hidden files and directories");
+    println!("  -g, --glob GLOB            With -r, only search files matching GLOB, or leave them out with !GLOB");
+    println!("  -t, --type TYPE            With -r, only search files of TYPE (like rust or js)");
+    println!("  -T, --type-not TYPE        With -r, leave out files of TYPE");
+    println!("      --type-add TYPE:GLOB   Add a file type, or add GLOB to an existing one");
+    println!("      --type-list            Show the file types and their globs and exit");
+    println!("      --binary-files TYPE    What to do with binary files: binary (only say that it matches),");
+    println!("                             without-match (skip it) or text (search it like any other)");
+    println!("      --encoding NAME        Read the input as utf-8, utf-16le, utf-16be, latin1 or windows-1252");
+    println!("                             (by default UTF-16 is detected by its byte order mark)");
+    println!("  -z, --search-zip           Decompress gzip files (found by their first bytes) and search the text");
+    println!("                             (the members of a .tar.gz too, tar archives are always searched)");
+    println!("  -A, --after-context NUM    Show NUM lines of context after each match");
+    println!("  -B, --before-context NUM   Show NUM lines of context before each match");
+    println!("  -C, --context NUM          Show NUM lines of context before and after each match");
+    println!("  -c, --count                Only print the number of matching lines per file");
+    println!("      --count-matches        Only print the number of matches per file");
+    println!("      --stats                Print a summary of the search to stderr");
+    println!("  -o, --only-matching        Only print the matched parts of lines, each on its own line");
+    println!("      --column               With -o, show the column each match starts at");
+    println!("  -b, --byte-offset          Show the byte offset of each line (with -o, of each match), counted in");
+    println!("                             the text after -z decompresses it or --encoding transcodes it");
+    println!("      --json                 Print the results as JSON Lines, one event per line");
+    println!("  -q, --quiet                Print nothing, and stop at the first match");
+    println!("  -s, --no-messages          Do not print errors about files that can not be read");
+    println!("  -l, --files-with-matches   Only print the paths of files with a selected line");
+    println!("  -L, --files-without-match  Only print the paths of files without a selected line");
+    println!("  -0, --null                 End the paths printed by -l and -L with a NUL byte");
+    println!("  -m, --max-count NUM        Stop reading a file after NUM selected lines");
+    println!("  -j, --threads NUM          Search NUM files at the same time (0 for one per CPU)");
+    println!("      --sort path            Print the files in path order, even with several threads");
+    println!("      --help                 Show this help message and exit");
+    println!();
+    println!("Short flags can be combined (-in), values can be attached (-A1, --context=1),");
+    println!("and everything after -- is taken as the pattern or a file, even if it starts with '-'.");
+    println!();
+    println!("Exit status is 0 if any line was selected, 1 if none was, and 2 if an error occurred.");
+    println!("With -q, a selected line gives 0 even if an error occurred.");
+}
+
+// What to do with a file that has a NUL byte near the start, which grep takes to be binary
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryFiles {
+    // Only print that the file matches, instead of its lines
+    Binary,
+    // Treat the file as if nothing in it matched
+    WithoutMatch,
+    // Search and print the file like any other
+    Text,
 }
 
 pub struct Config {
-    pub query: String,
-    pub file_path: String,
+    pub patterns: Vec<String>,
+    pub pattern_files: Vec<String>,
+    pub file_paths: Vec<String>,
     pub ignore_case: bool,
     pub show_line_numbers: bool,
     pub only_match_words: bool,
     pub inverted_match: bool,
+    pub use_regex: bool,
+    pub recursive: bool,
+    pub before_context: usize,
+    pub after_context: usize,
+    pub count: bool,
+    pub count_matches: bool,
+    pub show_stats: bool,
+    pub only_matching: bool,
+    pub show_column: bool,
+    pub json: bool,
+    pub quiet: bool,
+    pub no_messages: bool,
+    pub files_with_matches: bool,
+    pub files_without_match: bool,
+    pub null: bool,
+    pub max_count: Option<usize>,
+    // The number of files searched at the same time
+    pub threads: usize,
+    // With several threads, print the files in the order they are walked instead of as they finish
+    pub sort_by_path: bool,
+    // With -r, also search what .gitignore, .ignore and .git/info/exclude ignore
+    pub no_ignore: bool,
+    // With -r, also search hidden files and directories
+    pub hidden: bool,
+    // With -r, the files left out by --glob, -t and -T
+    pub file_filter: PathFilter,
+    // The built-in file types and the ones added with --type-add
+    pub file_types: FileTypes,
+    // --type-list only prints the file types, without searching anything
+    pub type_list: bool,
+    pub binary_files: BinaryFiles,
+    pub show_byte_offset: bool,
+    // None reads UTF-8, or the encoding a byte order mark at the start of the input is for
+    pub encoding: Option<Encoding>,
+    pub search_zip: bool,
 }
 
-impl Config {
-    pub fn new(
-        query: String,
-        file_path: String,
-        ignore_case: bool,
-        show_line_numbers: bool,
-        only_match_words: bool,
-        inverted_match: bool,
-    ) -> Self {
+// The config of a search without any options, so a config can be made with only the fields that
+// differ given by name, like `Config { patterns, ..Config::default() }`
+impl Default for Config {
+    fn default() -> Self {
         Self {
-            query,
-            file_path,
-            ignore_case,
-            show_line_numbers,
-            only_match_words,
-            inverted_match,
+            patterns: Vec::new(),
+            pattern_files: Vec::new(),
+            file_paths: Vec::new(),
+            ignore_case: false,
+            show_line_numbers: false,
+            only_match_words: false,
+            inverted_match: false,
+            use_regex: false,
+            recursive: false,
+            before_context: 0,
+            after_context: 0,
+            count: false,
+            count_matches: false,
+            show_stats: false,
+            only_matching: false,
+            show_column: false,
+            json: false,
+            quiet: false,
+            no_messages: false,
+            files_with_matches: false,
+            files_without_match: false,
+            null: false,
+            max_count: None,
+            threads: 1,
+            sort_by_path: false,
+            no_ignore: false,
+            hidden: false,
+            file_filter: PathFilter::new(),
+            file_types: FileTypes::new(),
+            type_list: false,
+            binary_files: BinaryFiles::Binary,
+            show_byte_offset: false,
+            encoding: None,
+            search_zip: false,
         }
     }
+}
 
-    pub fn build(args: &[String]) -> Result<Self, &'static str> {
-        if args.len() < 3 {
-            return Err("Not enough arguments");
+impl Config {
+    pub fn build(args: &[String]) -> Result<Self, MinigrepError> {
+        if args.len() < 2 {
+            return Err(MinigrepError::MissingPattern);
         }
 
-        let query: String = args[&args.len() - 2].clone(); // Second last arg
-        match query.chars().next() {
-            Some('-') => return Err("Query argument missing"),
-            None => return Err("Query can not be empty"),
-            _ => {},
+        let mut patterns: Vec<String> = Vec::new();
+        let mut pattern_files: Vec<String> = Vec::new();
+        let mut flags: Vec<&str> = Vec::new();
+        let mut positional_args: Vec<String> = Vec::new();
+        let mut before_context: Option<usize> = None;
+        let mut after_context: Option<usize> = None;
+        let mut context: Option<usize> = None;
+        let mut max_count: Option<usize> = None;
+        let mut threads: usize = 1;
+        let mut sort_by_path = false;
+        let mut file_filter = PathFilter::new();
+        let mut file_types = FileTypes::new();
+        // Types are only looked up once all of them are added, so --type-add can come after -t
+        let mut type_selections: Vec<(String, String, bool)> = Vec::new();
+        let mut binary_files = BinaryFiles::Binary;
+        let mut encoding = None;
+
+        // Skip the first arg since it is the program name
+        for arg in args::parse(&args[1..], OPTIONS)? {
+            match arg {
+                // The parser always gives a value to the options that take one. Values that are wrong
+                // are reported with the flag as it was written, like -B or --before-context.
+                Arg::Option("regexp", _, Some(pattern)) => patterns.push(pattern),
+                Arg::Option("file", _, Some(pattern_file)) => pattern_files.push(pattern_file),
+                Arg::Option("after-context", flag, value) => after_context = Some(parse_line_count(&flag, value)?),
+                Arg::Option("before-context", flag, value) => before_context = Some(parse_line_count(&flag, value)?),
+                Arg::Option("context", flag, value) => context = Some(parse_line_count(&flag, value)?),
+                Arg::Option("max-count", flag, value) => max_count = Some(parse_line_count(&flag, value)?),
+                Arg::Option("threads", flag, value) => threads = parse_threads(&flag, value)?,
+                Arg::Option("sort", flag, value) => sort_by_path = parse_sort(&flag, value)?,
+                Arg::Option("glob", _, Some(glob)) => file_filter.add_glob(&glob),
+                Arg::Option("type", flag, Some(name)) => type_selections.push((name, flag, true)),
+                Arg::Option("type-not", flag, Some(name)) => type_selections.push((name, flag, false)),
+                Arg::Option("type-add", _, Some(definition)) => file_types.add(&definition)?,
+                Arg::Option("binary-files", flag, value) => binary_files = parse_binary_files(&flag, value)?,
+                Arg::Option("encoding", _, Some(label)) => encoding = Some(Encoding::from_label(&label)?),
+                Arg::Option(name, _, _) => flags.push(name),
+                Arg::Positional(arg) => positional_args.push(arg),
+            }
         }
-        
+        let mut positional_args = positional_args.into_iter();
+
+        for (name, flag, selected) in &type_selections {
+            file_filter
+                .add_type(&file_types, name, *selected)
+                .map_err(|error| match error {
+                    MinigrepError::BadFlag { message, .. } => MinigrepError::BadFlag { flag: flag.clone(), message },
+                    error => error,
+                })?;
+        }
+
+        // --type-list does not search, so it needs no pattern
+        let type_list = flags.contains(&"type-list");
 
-        let file_path: String = args[&args.len() - 1].clone(); // Last arg
-        match file_path.chars().next() {
-            Some('-') => return Err("File path argument missing"),
-            None => return Err("File path can not be empty"),
-            _ => {},
+        // The pattern is only given positionally when neither -e nor -f is used
+        if patterns.is_empty() && pattern_files.is_empty() && !type_list {
+            match positional_args.next() {
+                Some(query) => patterns.push(query),
+                None => return Err(MinigrepError::MissingPattern),
+            }
+        }
+        if patterns.iter().any(|pattern| pattern.is_empty()) {
+            return Err(MinigrepError::BadArguments("Query can not be empty"));
         }
 
-        let ignore_case = if args.contains(&String::from("-i")) {
+        // Every remaining positional arg is a file or directory to search.
+        // With none of them, standard input is searched instead.
+        let file_paths: Vec<String> = positional_args.collect();
+        if file_paths.iter().any(|file_path| file_path.is_empty()) {
+            return Err(MinigrepError::BadArguments("File path can not be empty"));
+        }
+
+        let ignore_case = if flags.contains(&"ignore-case") {
             true
         } else {
             env::var("IGNORE_CASE").is_ok()
         };
 
-        let show_line_numbers = if args.contains(&String::from("-n")) {
+        let show_line_numbers = if flags.contains(&"line-number") {
             true
         } else {
             env::var("SHOW_LINE_NUMBERS").is_ok()
         };
 
-        let only_match_words = if args.contains(&String::from("-w")) {
+        let only_match_words = if flags.contains(&"word-regexp") {
             true
         } else {
             env::var("ONLY_MATCH_WORDS").is_ok()
         };
 
-        let inverted_match = if args.contains(&String::from("-v")) {
+        let inverted_match = if flags.contains(&"invert-match") {
             true
         } else {
             env::var("INVERTED_MATCH").is_ok()
         };
 
+        let use_regex = if flags.contains(&"extended-regexp") {
+            true
+        } else {
+            env::var("USE_REGEX").is_ok()
+        };
+
+        let recursive = flags.contains(&"recursive");
+        let no_ignore = flags.contains(&"no-ignore");
+        let hidden = flags.contains(&"hidden");
+
+        // Like in grep, -A and -B win over -C no matter which one comes first
+        let before_context = before_context.or(context).unwrap_or(0);
+        let after_context = after_context.or(context).unwrap_or(0);
+
+        let count = flags.contains(&"count");
+        let count_matches = flags.contains(&"count-matches");
+        let show_stats = flags.contains(&"stats");
+
+        let only_matching = flags.contains(&"only-matching");
+        let show_column = flags.contains(&"column");
+        let show_byte_offset = flags.contains(&"byte-offset");
+        let search_zip = flags.contains(&"search-zip");
+
+        let files_with_matches = flags.contains(&"files-with-matches");
+        let files_without_match = flags.contains(&"files-without-match");
+        if files_with_matches && files_without_match {
+            return Err(MinigrepError::BadArguments("-l and -L can not be used together"));
+        }
+        let null = flags.contains(&"null");
+
+        // A count or a list of files has no lines to turn into events, so they can not be used together
+        let json = flags.contains(&"json");
+        if json && (count || count_matches || files_with_matches || files_without_match) {
+            return Err(MinigrepError::BadArguments(
+                "--json can not be used with -c, --count-matches, -l or -L",
+            ));
+        }
+
+        let quiet = flags.contains(&"quiet");
+        let no_messages = flags.contains(&"no-messages");
+
         Ok(Self {
-            query,
-            file_path,
+            patterns,
+            pattern_files,
+            file_paths,
             ignore_case,
             show_line_numbers,
             only_match_words,
             inverted_match,
+            use_regex,
+            recursive,
+            before_context,
+            after_context,
+            count,
+            count_matches,
+            show_stats,
+            only_matching,
+            show_column,
+            json,
+            quiet,
+            no_messages,
+            files_with_matches,
+            files_without_match,
+            null,
+            max_count,
+            threads,
+            sort_by_path,
+            no_ignore,
+            hidden,
+            file_filter,
+            file_types,
+            type_list,
+            binary_files,
+            show_byte_offset,
+            encoding,
+            search_zip,
         })
     }
 }
 
+// Context lengths and -m are all a number of lines
+fn parse_line_count(flag: &str, value: Option<String>) -> Result<usize, MinigrepError> {
+    let value = value.unwrap_or_default();
+
+    value.parse().map_err(|_| MinigrepError::BadFlag {
+        flag: String::from(flag),
+        message: format!("needs a non-negative number of lines, not `{value}`"),
+    })
+}
+
+// Prints every file type with its globs, for --type-list. Like the search output, the list just
+// ends when whoever reads it goes away, like `head` does.
+pub fn print_type_list(out: &mut dyn Write, file_types: &FileTypes) -> Result<(), MinigrepError> {
+    for (name, globs) in file_types.iter() {
+        match writeln!(out, "{name}: {}", globs.join(", ")) {
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            Err(error) => return Err(MinigrepError::Output(error)),
+            Ok(()) => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_binary_files(flag: &str, value: Option<String>) -> Result<BinaryFiles, MinigrepError> {
+    match value.as_deref() {
+        Some("binary") => Ok(BinaryFiles::Binary),
+        Some("without-match") => Ok(BinaryFiles::WithoutMatch),
+        Some("text") => Ok(BinaryFiles::Text),
+        _ => Err(MinigrepError::BadFlag {
+            flag: String::from(flag),
+            message: format!("needs `binary`, `without-match` or `text`, not `{}`", value.unwrap_or_default()),
+        }),
+    }
+}
+
+// -j 0 uses one thread for every CPU
+fn parse_threads(flag: &str, value: Option<String>) -> Result<usize, MinigrepError> {
+    let value = value.unwrap_or_default();
+
+    match value.parse::<usize>() {
+        Ok(0) => Ok(thread::available_parallelism().map_or(1, |threads| threads.get())),
+        Ok(threads) => Ok(threads),
+        Err(_) => Err(MinigrepError::BadFlag {
+            flag: String::from(flag),
+            message: format!("needs a number of threads, not `{value}`"),
+        }),
+    }
+}
+
+// Files can only be sorted by path, and --sort=none turns it off again
+fn parse_sort(flag: &str, value: Option<String>) -> Result<bool, MinigrepError> {
+    match value.as_deref() {
+        Some("path") => Ok(true),
+        Some("none") => Ok(false),
+        _ => Err(MinigrepError::BadFlag {
+            flag: String::from(flag),
+            message: format!("needs `path` or `none`, not `{}`", value.unwrap_or_default()),
+        }),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::Path;
 
     #[test]
-    fn config_new_preserves_fields() {
-        let query = String::from("Rust!");
-        let file_path = String::from("src/main.rs");
-        let ignore_case = false;
-        let show_line_numbers = true;
-        let only_match_words = true;
-        let inverted_match = false;
+    fn config_default_is_build_without_options() {
+        let args: Vec<String> = ["minigrep", "Rust!", "src/main.rs"].into_iter().map(String::from).collect();
+        let built = Config::build(&args).unwrap();
+        let config = Config {
+            patterns: vec![String::from("Rust!")],
+            file_paths: vec![String::from("src/main.rs")],
+            ..Config::default()
+        };
 
-        let config = Config::new(
-            query.clone(),
-            file_path.clone(),
-            ignore_case,
-            show_line_numbers,
-            only_match_words,
-            inverted_match,
+        assert_eq!(built.patterns, config.patterns);
+        assert_eq!(built.pattern_files, config.pattern_files);
+        assert_eq!(built.file_paths, config.file_paths);
+        assert_eq!(
+            (built.ignore_case, built.show_line_numbers, built.only_match_words, built.inverted_match, built.use_regex),
+            (config.ignore_case, config.show_line_numbers, config.only_match_words, config.inverted_match, config.use_regex)
+        );
+        assert_eq!(
+            (built.recursive, built.before_context, built.after_context, built.count, built.count_matches),
+            (config.recursive, config.before_context, config.after_context, config.count, config.count_matches)
+        );
+        assert_eq!(
+            (built.show_stats, built.only_matching, built.show_column, built.json, built.quiet, built.no_messages),
+            (config.show_stats, config.only_matching, config.show_column, config.json, config.quiet, config.no_messages)
+        );
+        assert_eq!(
+            (built.files_with_matches, built.files_without_match, built.null, built.max_count),
+            (config.files_with_matches, config.files_without_match, config.null, config.max_count)
+        );
+        assert_eq!(
+            (built.threads, built.sort_by_path, built.no_ignore, built.hidden, built.type_list),
+            (config.threads, config.sort_by_path, config.no_ignore, config.hidden, config.type_list)
+        );
+        assert_eq!(built.file_filter, config.file_filter);
+        assert_eq!(built.file_types, config.file_types);
+        assert_eq!(
+            (built.binary_files, built.show_byte_offset, built.encoding, built.search_zip),
+            (config.binary_files, config.show_byte_offset, config.encoding, config.search_zip)
         );
-
-        assert_eq!(config.query, query);
-        assert_eq!(config.file_path, file_path);
-        assert_eq!(config.ignore_case, ignore_case);
-        assert_eq!(config.show_line_numbers, show_line_numbers);
-        assert_eq!(config.only_match_words, only_match_words);
-        assert_eq!(config.inverted_match, inverted_match);
     }
 
     #[test]
@@ -135,6 +516,7 @@ mod tests {
         let show_line_numbers = true;
         let only_match_words = false;
         let inverted_match = true;
+        let use_regex = true;
 
         let mut args: Vec<String> = vec!["src/main.rs".into()];
 
@@ -150,23 +532,26 @@ mod tests {
         if inverted_match {
             args.push(String::from("-v"));
         }
+        if use_regex {
+            args.push(String::from("-E"));
+        }
         
         args.push(query.clone()); // Needs to be second last
         args.push(file_path.clone()); // Needs to be last
 
         let config = Config::build(&args).unwrap();
 
-        assert_eq!(config.query, query);
-        assert_eq!(config.file_path, file_path);
+        assert_eq!(config.patterns, vec![query]);
+        assert_eq!(config.file_paths, vec![file_path]);
         assert_eq!(config.ignore_case, ignore_case);
         assert_eq!(config.show_line_numbers, show_line_numbers);
         assert_eq!(config.only_match_words, only_match_words);
         assert_eq!(config.inverted_match, inverted_match);
+        assert_eq!(config.use_regex, use_regex);
     }
 
     #[test]
     fn config_build_fails_if_missing_query() {
-        let file_path = String::from("poem.txt");
         let ignore_case = true;
         let show_line_numbers = true;
         let only_match_words = false;
@@ -188,8 +573,7 @@ mod tests {
         }
 
         // Here we are not pushing the query arg
-    
-        args.push(file_path.clone()); // Needs to be last
+        // Neither the file path, since a single positional arg would be the query with stdin as input
 
         let config = Config::build(&args); // This is missing the query
 
@@ -230,4 +614,388 @@ mod tests {
         // Should fail due file path being empty
         assert!(config.is_err());
     }
+
+    #[test]
+    fn config_build_collects_repeated_patterns() {
+        let args: Vec<String> = vec![
+            "src/main.rs".into(),
+            "-e".into(),
+            "ERR-1".into(),
+            "-i".into(),
+            "-e".into(),
+            "-v".into(), // A pattern can start with '-' when given with -e
+            "app.log".into(),
+        ];
+
+        let config = Config::build(&args).unwrap();
+
+        assert_eq!(config.patterns, vec![String::from("ERR-1"), String::from("-v")]);
+        assert_eq!(config.file_paths, vec![String::from("app.log")]);
+        assert!(config.ignore_case);
+        assert!(!config.inverted_match);
+    }
+
+    #[test]
+    fn config_build_collects_pattern_files() {
+        let args: Vec<String> = vec![
+            "src/main.rs".into(),
+            "-f".into(),
+            "codes.txt".into(),
+            "app.log".into(),
+        ];
+
+        let config = Config::build(&args).unwrap();
+
+        assert!(config.patterns.is_empty());
+        assert_eq!(config.pattern_files, vec![String::from("codes.txt")]);
+        assert_eq!(config.file_paths, vec![String::from("app.log")]);
+    }
+
+    #[test]
+    fn config_build_fails_if_pattern_option_has_no_value() {
+        let args: Vec<String> = vec!["src/main.rs".into(), "app.log".into(), "-e".into()];
+
+        let config = Config::build(&args);
+
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn config_build_collects_multiple_file_paths() {
+        let args: Vec<String> = vec![
+            "src/main.rs".into(),
+            "-r".into(),
+            "error".into(),
+            "a.txt".into(),
+            "b.txt".into(),
+            "logs/".into(),
+        ];
+
+        let config = Config::build(&args).unwrap();
+
+        assert_eq!(config.patterns, vec![String::from("error")]);
+        assert_eq!(
+            config.file_paths,
+            vec![String::from("a.txt"), String::from("b.txt"), String::from("logs/")]
+        );
+        assert!(config.recursive);
+    }
+
+    #[test]
+    fn config_build_reads_stdin_without_file_path() {
+        let args: Vec<String> = vec!["src/main.rs".into(), "-i".into(), "timeout".into()];
+
+        let config = Config::build(&args).unwrap();
+
+        assert_eq!(config.patterns, vec![String::from("timeout")]);
+        assert!(config.file_paths.is_empty());
+    }
+
+    #[test]
+    fn config_build_treats_dash_as_file_path() {
+        let args: Vec<String> = vec!["src/main.rs".into(), "timeout".into(), "-".into(), "app.log".into()];
+
+        let config = Config::build(&args).unwrap();
+
+        assert_eq!(config.file_paths, vec![String::from("-"), String::from("app.log")]);
+    }
+
+    #[test]
+    fn config_build_extracts_context_lengths() {
+        let args: Vec<String> = vec![
+            "src/main.rs".into(),
+            "-A".into(),
+            "1".into(),
+            "-C".into(),
+            "3".into(),
+            "panic".into(),
+            "app.log".into(),
+        ];
+
+        let config = Config::build(&args).unwrap();
+
+        assert_eq!(config.before_context, 3);
+        assert_eq!(config.after_context, 1);
+        assert_eq!(config.file_paths, vec![String::from("app.log")]);
+    }
+
+    #[test]
+    fn config_build_fails_if_context_length_is_not_a_number() {
+        let args: Vec<String> = vec!["src/main.rs".into(), "-B".into(), "two".into(), "panic".into()];
+
+        let config = Config::build(&args);
+
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn config_build_extracts_count_and_stats_flags() {
+        let args: Vec<String> = vec![
+            "src/main.rs".into(),
+            "--count-matches".into(),
+            "--stats".into(),
+            "panic".into(),
+            "app.log".into(),
+        ];
+
+        let config = Config::build(&args).unwrap();
+
+        assert!(!config.count);
+        assert!(config.count_matches);
+        assert!(config.show_stats);
+    }
+
+    #[test]
+    fn config_build_extracts_only_matching_flags() {
+        let args: Vec<String> = vec![
+            "src/main.rs".into(),
+            "-o".into(),
+            "--column".into(),
+            "-b".into(),
+            "-E".into(),
+            r"id=\d+".into(),
+            "app.log".into(),
+        ];
+
+        let config = Config::build(&args).unwrap();
+
+        assert!(config.only_matching);
+        assert!(config.show_column);
+        assert!(config.show_byte_offset);
+        assert_eq!(config.patterns, vec![String::from(r"id=\d+")]);
+    }
+
+    #[test]
+    fn config_build_extracts_json_flag() {
+        let args: Vec<String> = vec!["src/main.rs".into(), "--json".into(), "error".into()];
+
+        let config = Config::build(&args).unwrap();
+
+        assert!(config.json);
+    }
+
+    #[test]
+    fn config_build_fails_if_json_is_used_with_count() {
+        let args: Vec<String> = vec!["src/main.rs".into(), "--json".into(), "-c".into(), "error".into()];
+
+        let result = Config::build(&args);
+
+        assert!(result.is_err());
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        let mut all_args = vec![String::from("src/main.rs")];
+        all_args.extend(args.iter().map(|arg| String::from(*arg)));
+        all_args
+    }
+
+    #[test]
+    fn config_build_accepts_flags_after_positional_args() {
+        let config = Config::build(&args(&["foo", "file", "-n"])).unwrap();
+
+        assert_eq!(config.patterns, vec![String::from("foo")]);
+        assert_eq!(config.file_paths, vec![String::from("file")]);
+        assert!(config.show_line_numbers);
+    }
+
+    #[test]
+    fn config_build_accepts_clustered_and_long_flags() {
+        let config = Config::build(&args(&["-in", "--invert-match", "--context=2", "-A1", "foo"])).unwrap();
+
+        assert!(config.ignore_case);
+        assert!(config.show_line_numbers);
+        assert!(config.inverted_match);
+        assert_eq!(config.before_context, 2);
+        assert_eq!(config.after_context, 1);
+    }
+
+    #[test]
+    fn config_build_takes_args_after_double_dash_as_positional() {
+        let config = Config::build(&args(&["--", "-v", "file"])).unwrap();
+
+        assert_eq!(config.patterns, vec![String::from("-v")]);
+        assert_eq!(config.file_paths, vec![String::from("file")]);
+        assert!(!config.inverted_match);
+    }
+
+    #[test]
+    fn config_build_fails_on_unknown_option() {
+        assert!(Config::build(&args(&["-iQ", "foo"])).is_err());
+
+        let error = Config::build(&args(&["--ignore", "foo"])).err().unwrap();
+        assert_eq!("Unknown option `--ignore`, did you mean `--ignore-case`?", error.to_string());
+    }
+
+    #[test]
+    fn config_build_error_names_option_with_bad_value() {
+        let error = |arguments: &[&str]| Config::build(&args(arguments)).err().unwrap().to_string();
+
+        // The option is named the way it was written
+        assert_eq!("Option `-B` needs a non-negative number of lines, not `two`", error(&["-B", "two", "foo"]));
+        assert_eq!("Option `-B` needs a non-negative number of lines, not `two`", error(&["-nBtwo", "foo"]));
+        assert_eq!(
+            "Option `--before-context` needs a non-negative number of lines, not `two`",
+            error(&["--before-context=two", "foo"])
+        );
+        assert_eq!("Option `-j` needs a number of threads, not `many`", error(&["-j", "many", "foo"]));
+        assert!(error(&["-t", "klingon", "foo"]).starts_with("Option `-t` needs a known file type"));
+        assert!(error(&["--type-not", "klingon", "foo"]).starts_with("Option `--type-not` needs a known file type"));
+        assert_eq!("Option `-A` requires a value", error(&["foo", "-A"]));
+    }
+
+    #[test]
+    fn is_help_requested_stops_at_double_dash() {
+        assert!(is_help_requested(&args(&["-i", "--help"])));
+        assert!(!is_help_requested(&args(&["--", "--help"])));
+    }
+
+    #[test]
+    fn is_help_requested_ignores_help_as_option_value() {
+        assert!(!is_help_requested(&args(&["-e", "--help", "file.txt"])));
+        assert!(!is_help_requested(&args(&["--regexp", "--help"])));
+        assert!(!is_help_requested(&args(&["-ie", "--help"])));
+        assert!(is_help_requested(&args(&["-e", "foo", "--help"])));
+
+        let config = Config::build(&args(&["-e", "--help", "file.txt"])).unwrap();
+        assert_eq!(vec![String::from("--help")], config.patterns);
+    }
+
+    #[test]
+    fn config_build_extracts_quiet_and_no_messages_flags() {
+        let config = Config::build(&args(&["-qs", "foo", "a.txt"])).unwrap();
+
+        assert!(config.quiet);
+        assert!(config.no_messages);
+    }
+
+    #[test]
+    fn config_build_extracts_file_listing_flags() {
+        let config = Config::build(&args(&["-l0", "foo", "a.txt", "b.txt"])).unwrap();
+
+        assert!(config.files_with_matches);
+        assert!(!config.files_without_match);
+        assert!(config.null);
+    }
+
+    #[test]
+    fn config_build_fails_if_listing_files_with_and_without_matches() {
+        assert!(Config::build(&args(&["-l", "-L", "foo"])).is_err());
+    }
+
+    #[test]
+    fn config_build_extracts_max_count() {
+        let config = Config::build(&args(&["foo", "-m", "5"])).unwrap();
+        assert_eq!(config.max_count, Some(5));
+
+        let config = Config::build(&args(&["foo"])).unwrap();
+        assert_eq!(config.max_count, None);
+    }
+
+    #[test]
+    fn config_build_extracts_threads_and_sort() {
+        let config = Config::build(&args(&["-j4", "--sort=path", "foo", "src"])).unwrap();
+        assert_eq!(config.threads, 4);
+        assert!(config.sort_by_path);
+
+        let config = Config::build(&args(&["foo"])).unwrap();
+        assert_eq!(config.threads, 1);
+        assert!(!config.sort_by_path);
+
+        let config = Config::build(&args(&["-j", "0", "foo"])).unwrap();
+        assert!(config.threads >= 1);
+    }
+
+    #[test]
+    fn config_build_fails_on_bad_threads_or_sort() {
+        assert!(Config::build(&args(&["-j", "many", "foo"])).is_err());
+        assert!(Config::build(&args(&["--sort=size", "foo"])).is_err());
+    }
+
+    #[test]
+    fn config_build_extracts_walk_overrides() {
+        let config = Config::build(&args(&["-r", "--no-ignore", "--hidden", "foo"])).unwrap();
+        assert!(config.no_ignore);
+        assert!(config.hidden);
+
+        let config = Config::build(&args(&["-r", "foo"])).unwrap();
+        assert!(!config.no_ignore);
+        assert!(!config.hidden);
+    }
+
+    #[test]
+    fn config_build_extracts_globs_and_types() {
+        let config = Config::build(&args(&["-r", "-g", "*.rs", "--glob=!*_test.rs", "-t", "rust", "--type-add", "proto:*.proto", "foo"])).unwrap();
+
+        assert!(config.file_filter.allows(Path::new("src/main.rs"), false));
+        assert!(!config.file_filter.allows(Path::new("src/main_test.rs"), false));
+        assert_eq!(Some(&[String::from("*.proto")][..]), config.file_types.globs("proto"));
+    }
+
+    #[test]
+    fn config_build_fails_on_unknown_type() {
+        assert!(Config::build(&args(&["-t", "klingon", "foo"])).is_err());
+        assert!(Config::build(&args(&["--type-add", "klingon", "foo"])).is_err());
+    }
+
+    #[test]
+    fn config_build_needs_no_pattern_for_type_list() {
+        let config = Config::build(&args(&["--type-list"])).unwrap();
+
+        assert!(config.type_list);
+    }
+
+    #[test]
+    fn print_type_list_stops_quietly_when_pipe_is_closed() {
+        // Takes one line, like `head -1`, and then goes away
+        struct ClosingPipe(usize);
+        impl Write for ClosingPipe {
+            fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+                if self.0 > 0 {
+                    return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+                }
+                self.0 += buffer.iter().filter(|&&byte| byte == b'\n').count();
+                Ok(buffer.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let file_types = FileTypes::new();
+        assert!(print_type_list(&mut ClosingPipe(0), &file_types).is_ok());
+
+        let mut out = Vec::new();
+        print_type_list(&mut out, &file_types).unwrap();
+        let list = String::from_utf8(out).unwrap();
+        assert_eq!(file_types.iter().count(), list.lines().count());
+        assert!(list.lines().any(|line| line.starts_with("rust: ")));
+    }
+
+    #[test]
+    fn config_build_extracts_binary_files() {
+        let config = Config::build(&args(&["--binary-files=without-match", "foo"])).unwrap();
+        assert_eq!(config.binary_files, BinaryFiles::WithoutMatch);
+
+        let config = Config::build(&args(&["foo"])).unwrap();
+        assert_eq!(config.binary_files, BinaryFiles::Binary);
+
+        assert!(Config::build(&args(&["--binary-files", "maybe", "foo"])).is_err());
+    }
+
+    #[test]
+    fn config_build_extracts_encoding() {
+        let config = Config::build(&args(&["--encoding", "utf-16le", "foo"])).unwrap();
+        assert_eq!(config.encoding, Some(Encoding::Utf16Le));
+
+        let config = Config::build(&args(&["foo"])).unwrap();
+        assert_eq!(config.encoding, None);
+
+        assert!(Config::build(&args(&["--encoding=klingon", "foo"])).is_err());
+    }
+
+    #[test]
+    fn config_build_extracts_search_zip() {
+        assert!(Config::build(&args(&["-z", "foo", "app.log.gz"])).unwrap().search_zip);
+        assert!(!Config::build(&args(&["foo"])).unwrap().search_zip);
+    }
 }
@@ -1,21 +1,224 @@
-use crate::model::{Content, Line};
+use std::io::{self, Write};
+
+use crate::model::{Content, Line, LineKind};
+use crate::stats::Stats;
+
+// Everything is printed to the given output, which is stdout or, when files are searched on several
+// threads, a buffer that holds the output of one file until it can be printed in one piece
 
 impl Content {
-    pub fn display(&self, show_line_numbers: bool) {
+    // The file name is printed in front of every line when several files are searched.
+    // With show_separators, groups of lines that are not next to each other are separated by "--",
+    // which is how grep shows where the context around one match ends and the next begins.
+    pub fn display(
+        &self,
+        out: &mut dyn Write,
+        file_name: Option<&str>,
+        show_line_numbers: bool,
+        show_separators: bool,
+    ) -> io::Result<()> {
+        let mut previous_index: Option<usize> = None;
+
         for line in &self.lines {
-            line.display(show_line_numbers);
+            if show_separators && previous_index.is_some_and(|previous| line.index != previous + 1) {
+                display_separator(out)?;
+            }
+
+            line.display(out, file_name, show_line_numbers, false)?;
+            previous_index = Some(line.index);
         }
+
+        Ok(())
     }
 }
 
 impl Line {
-    pub fn display(&self, show_line_numbers: bool) {
-        let text = &self.text;
+    // With show_byte_offset, the offset of the line in the input is printed after the line number
+    pub fn display(
+        &self,
+        out: &mut dyn Write,
+        file_name: Option<&str>,
+        show_line_numbers: bool,
+        show_byte_offset: bool,
+    ) -> io::Result<()> {
+        let text = self.highlight();
+
+        // Like grep, selected lines use ':' after the file name and line number, and context lines use '-'
+        let marker = match self.kind {
+            LineKind::Match => ':',
+            LineKind::Context => '-',
+        };
+
+        let mut prefix = match file_name {
+            Some(file_name) => format!("{file_name}{marker}"),
+            None => String::new(),
+        };
+        if show_line_numbers {
+            prefix.push_str(&format!("{}{marker}", self.index));
+        }
+        if show_byte_offset {
+            prefix.push_str(&format!("{}{marker}", self.byte_offset));
+        }
+
+        // A space separates the numbers from the text
+        if show_line_numbers || show_byte_offset {
+            prefix.push(' ');
+        }
+
+        writeln!(out, "{prefix}{text}")
+    }
+
+    // Prints each match on its own line for -o, with the column (in chars, starting at 1)
+    // of where the match starts when show_column is set, and its offset in the input in bytes
+    // when show_byte_offset is set
+    pub fn display_only_matching(
+        &self,
+        out: &mut dyn Write,
+        file_name: Option<&str>,
+        show_line_numbers: bool,
+        show_column: bool,
+        show_byte_offset: bool,
+    ) -> io::Result<()> {
+        for found_match in &self.matches {
+            let mut prefix = match file_name {
+                Some(file_name) => format!("{file_name}:"),
+                None => String::new(),
+            };
             if show_line_numbers {
-                let index = self.index;
-                println!("{index}: {text}");
-            } else {
-                println!("{text}")
+                prefix.push_str(&format!("{}:", self.index));
             }
+            if show_column {
+                prefix.push_str(&format!("{}:", found_match.char_range.start + 1));
+            }
+            if show_byte_offset {
+                prefix.push_str(&format!("{}:", self.byte_offset + found_match.byte_range.start as u64));
+            }
+
+            // Like the normal output, a space separates the numbers from the text
+            if show_line_numbers || show_column || show_byte_offset {
+                prefix.push(' ');
+            }
+
+            let text = String::from_utf8_lossy(&self.text[found_match.byte_range.clone()]);
+            writeln!(out, "{prefix}{text}")?;
+        }
+
+        Ok(())
     }
-}
\ No newline at end of file
+}
+
+// Prints a path for -l and -L. With --null it ends with a NUL byte instead of a line break,
+// so paths with line breaks in them can be piped to xargs -0 safely.
+pub fn display_path(out: &mut dyn Write, path: &str, null: bool) -> io::Result<()> {
+    if null {
+        write!(out, "{path}\0")
+    } else {
+        writeln!(out, "{path}")
+    }
+}
+
+pub fn display_separator(out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "--")
+}
+
+// Tells that a binary file has a selected line, instead of printing the line
+pub fn display_binary_match(out: &mut dyn Write, path: &str) -> io::Result<()> {
+    writeln!(out, "Binary file {path} matches")
+}
+
+// Prints the count for -c and --count-matches, after the file name when several files are searched
+pub fn display_count(out: &mut dyn Write, file_name: Option<&str>, count: u64) -> io::Result<()> {
+    match file_name {
+        Some(file_name) => writeln!(out, "{file_name}:{count}"),
+        None => writeln!(out, "{count}"),
+    }
+}
+
+impl Stats {
+    // The summary goes to stderr, so it never mixes with the matches when the output is piped
+    pub fn display(&self) {
+        eprintln!();
+        eprintln!("{} files scanned", self.files_scanned);
+        eprintln!("{} bytes read", self.bytes_read);
+        eprintln!("{} lines matched", self.lines_matched);
+        eprintln!("{} matches", self.matches);
+        eprintln!("{:.3} seconds elapsed", self.elapsed.as_secs_f64());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Match;
+
+    fn written(display: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> String {
+        let mut out = Vec::new();
+        display(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn line_display_marks_selected_and_context_lines() {
+        let selected = Line::new(12, "selected");
+        let context = Line::context(13, "context");
+
+        assert_eq!("12: selected\n", written(|out| selected.display(out, None, true, false)));
+        assert_eq!("13- context\n", written(|out| context.display(out, None, true, false)));
+        assert_eq!("app.log:12: selected\n", written(|out| selected.display(out, Some("app.log"), true, false)));
+        assert_eq!("app.log-context\n", written(|out| context.display(out, Some("app.log"), false, false)));
+    }
+
+    #[test]
+    fn line_display_shows_byte_offset_after_line_number() {
+        let mut context = Line::context(3, "context");
+        context.byte_offset = 40;
+
+        assert_eq!("app.log-3-40- context\n", written(|out| context.display(out, Some("app.log"), true, true)));
+        assert_eq!("40- context\n", written(|out| context.display(out, None, false, true)));
+    }
+
+    #[test]
+    fn line_display_highlights_matches() {
+        let mut line = Line::new(1, "an error here");
+        line.matches = vec![Match::new(1, 3..8, 3..8)];
+
+        assert_eq!("an \x1b[31merror\x1b[0m here\n", written(|out| line.display(out, None, false, false)));
+    }
+
+    #[test]
+    fn content_display_separates_groups_that_are_not_adjacent() {
+        let content = Content::from_lines(vec![
+            Line::context(1, "one"),
+            Line::new(2, "two"),
+            Line::context(5, "five"),
+            Line::new(6, "six"),
+        ]);
+
+        assert_eq!(
+            "1- one\n2: two\n--\n5- five\n6: six\n",
+            written(|out| content.display(out, None, true, true))
+        );
+        assert_eq!(
+            "1- one\n2: two\n5- five\n6: six\n",
+            written(|out| content.display(out, None, true, false))
+        );
+    }
+
+    #[test]
+    fn display_only_matching_prints_each_match() {
+        let mut line = Line::new(4, "id=1 id=22");
+        line.byte_offset = 100;
+        line.matches = vec![Match::new(4, 0..4, 0..4), Match::new(4, 5..10, 5..10)];
+
+        assert_eq!(
+            "a:4:1:100: id=1\na:4:6:105: id=22\n",
+            written(|out| line.display_only_matching(out, Some("a"), true, true, true))
+        );
+    }
+
+    #[test]
+    fn display_path_ends_with_line_break_or_nul() {
+        assert_eq!("src/main.rs\n", written(|out| display_path(out, "src/main.rs", false)));
+        assert_eq!("src/main.rs\0", written(|out| display_path(out, "src/main.rs", true)));
+    }
+}
@@ -0,0 +1,266 @@
+use std::io::{self, Read};
+
+use crate::error::MinigrepError;
+
+// The text encodings an input can be transcoded from. Everything is searched as UTF-8 bytes, so an
+// input in another encoding is turned into UTF-8 as it is read, before it is split into lines.
+// That way the matches, the line numbers and the printed text are all right, even for UTF-16,
+// where every other byte of an ASCII text is a NUL and a line break is two bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    // ISO-8859-1, where every byte is the char with the same number
+    Latin1,
+    // Latin-1 with printable chars (like € and curly quotes) instead of most of the controls at 0x80 to 0x9F
+    Windows1252,
+}
+
+// The chars of the bytes 0x80 to 0x9F in Windows-1252. The five bytes without a char are mapped to the
+// control with the same number, like in the WHATWG encoding standard that browsers follow.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+impl Encoding {
+    pub fn from_label(label: &str) -> Result<Self, MinigrepError> {
+        match label.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(Self::Utf8),
+            "utf-16le" | "utf16le" => Ok(Self::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(Self::Utf16Be),
+            "latin1" | "latin-1" | "iso-8859-1" => Ok(Self::Latin1),
+            "windows-1252" | "cp1252" => Ok(Self::Windows1252),
+            _ => Err(MinigrepError::BadFlag {
+                flag: String::from("--encoding"),
+                message: format!(
+                    "needs `utf-8`, `utf-16le`, `utf-16be`, `latin1` or `windows-1252`, not `{label}`"
+                ),
+            }),
+        }
+    }
+
+    // The byte order mark a text in this encoding can start with
+    pub fn bom(&self) -> &'static [u8] {
+        match self {
+            Self::Utf8 => b"\xEF\xBB\xBF",
+            Self::Utf16Le => b"\xFF\xFE",
+            Self::Utf16Be => b"\xFE\xFF",
+            Self::Latin1 | Self::Windows1252 => b"",
+        }
+    }
+
+    // Turns a whole text in this encoding into UTF-8, like for a text that is already in memory
+    // before it is handed to Content::from_bytes
+    pub fn decode(&self, bytes: &[u8]) -> Vec<u8> {
+        let mut decoded = Vec::with_capacity(bytes.len());
+        self.decode_into(bytes, &mut decoded, true);
+        decoded
+    }
+
+    // Decodes as much of the input as can be decoded and adds the UTF-8 to the output, and returns how
+    // many bytes of the input were used. A char that is cut off at the end of the input is left for the
+    // next call, unless this is the last part of the input, where it becomes a U+FFFD replacement char.
+    // Like the search does for UTF-8, anything that is not valid is replaced by U+FFFD.
+    fn decode_into(&self, input: &[u8], output: &mut Vec<u8>, is_last: bool) -> usize {
+        match self {
+            // UTF-8 is searched as the bytes it is
+            Self::Utf8 => {
+                output.extend_from_slice(input);
+                input.len()
+            }
+            Self::Latin1 => {
+                for &byte in input {
+                    push_char(output, byte as char);
+                }
+                input.len()
+            }
+            Self::Windows1252 => {
+                for &byte in input {
+                    let c = match byte {
+                        0x80..=0x9F => WINDOWS_1252_HIGH[usize::from(byte - 0x80)],
+                        _ => byte as char,
+                    };
+                    push_char(output, c);
+                }
+                input.len()
+            }
+            Self::Utf16Le | Self::Utf16Be => self.decode_utf16(input, output, is_last),
+        }
+    }
+
+    fn decode_utf16(&self, input: &[u8], output: &mut Vec<u8>, is_last: bool) -> usize {
+        let to_unit = |pair: &[u8]| match self {
+            Self::Utf16Be => u16::from_be_bytes([pair[0], pair[1]]),
+            _ => u16::from_le_bytes([pair[0], pair[1]]),
+        };
+
+        let mut usable = input.len() - input.len() % 2;
+        // A high surrogate at the end needs the low surrogate that comes with the next part of the input
+        if !is_last && usable >= 2 && (0xD800..0xDC00).contains(&to_unit(&input[usable - 2..usable])) {
+            usable -= 2;
+        }
+
+        let units = input[..usable].chunks_exact(2).map(to_unit);
+        for c in char::decode_utf16(units) {
+            push_char(output, c.unwrap_or(char::REPLACEMENT_CHARACTER));
+        }
+
+        if is_last && usable < input.len() {
+            // Half a code unit at the very end
+            push_char(output, char::REPLACEMENT_CHARACTER);
+            return input.len();
+        }
+
+        usable
+    }
+}
+
+// Looks for a byte order mark at the start of the input, and returns the encoding it is for
+pub fn sniff_bom(bytes: &[u8]) -> Option<Encoding> {
+    [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be]
+        .into_iter()
+        .find(|encoding| bytes.starts_with(encoding.bom()))
+}
+
+fn push_char(output: &mut Vec<u8>, c: char) {
+    let mut buffer = [0; 4];
+    output.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+}
+
+// Transcodes a reader in some encoding to UTF-8 as it is read, a block at a time, so a big input is
+// never in memory as a whole. The byte order mark is expected to be gone from the reader already.
+pub struct DecodingReader<R> {
+    reader: R,
+    encoding: Encoding,
+    // Bytes read from the reader that are not decoded yet, like the first half of a UTF-16 code unit
+    input: Vec<u8>,
+    // Decoded bytes that did not fit into the buffer of the last read
+    output: Vec<u8>,
+    output_position: usize,
+    finished: bool,
+}
+
+const DECODING_BLOCK_SIZE: usize = 64 * 1024;
+
+impl<R: Read> DecodingReader<R> {
+    pub fn new(reader: R, encoding: Encoding) -> Self {
+        Self {
+            reader,
+            encoding,
+            input: Vec::new(),
+            output: Vec::new(),
+            output_position: 0,
+            finished: false,
+        }
+    }
+
+    // Reads the next block and decodes it. At the end of the reader, the leftover bytes are decoded too.
+    fn decode_block(&mut self) -> io::Result<()> {
+        let kept = self.input.len();
+        self.input.resize(kept + DECODING_BLOCK_SIZE, 0);
+        let read = match self.reader.read(&mut self.input[kept..]) {
+            Ok(read) => read,
+            Err(error) => {
+                self.input.truncate(kept);
+                return Err(error);
+            }
+        };
+        self.input.truncate(kept + read);
+        self.finished = read == 0;
+
+        self.output.clear();
+        self.output_position = 0;
+        let used = self.encoding.decode_into(&self.input, &mut self.output, self.finished);
+        self.input.drain(..used);
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecodingReader<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        while self.output_position == self.output.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            self.decode_block()?;
+        }
+
+        let decoded = &self.output[self.output_position..];
+        let length = decoded.len().min(buffer.len());
+        buffer[..length].copy_from_slice(&decoded[..length]);
+        self.output_position += length;
+
+        Ok(length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn encoding_decodes_utf16_in_both_byte_orders() {
+        let be: Vec<u8> = "a€😀\n".encode_utf16().flat_map(u16::to_be_bytes).collect();
+
+        assert_eq!("a€😀\n".as_bytes(), Encoding::Utf16Le.decode(&utf16le("a€😀\n")));
+        assert_eq!("a€😀\n".as_bytes(), Encoding::Utf16Be.decode(&be));
+    }
+
+    #[test]
+    fn encoding_replaces_broken_utf16() {
+        // A lone low surrogate, and half a code unit at the end
+        assert_eq!("\u{FFFD}a\u{FFFD}".as_bytes(), Encoding::Utf16Le.decode(b"\x00\xDCa\x00\x62"));
+    }
+
+    #[test]
+    fn encoding_decodes_single_byte_encodings() {
+        assert_eq!("café \u{80}".as_bytes(), Encoding::Latin1.decode(b"caf\xe9 \x80"));
+        assert_eq!("café €“”\u{81}".as_bytes(), Encoding::Windows1252.decode(b"caf\xe9 \x80\x93\x94\x81"));
+    }
+
+    #[test]
+    fn encoding_from_label_accepts_known_names() {
+        assert_eq!(Encoding::Utf16Le, Encoding::from_label("UTF-16LE").unwrap());
+        assert_eq!(Encoding::Windows1252, Encoding::from_label("cp1252").unwrap());
+        assert!(Encoding::from_label("ebcdic").is_err());
+    }
+
+    #[test]
+    fn sniff_bom_finds_encoding() {
+        assert_eq!(Some(Encoding::Utf8), sniff_bom(b"\xEF\xBB\xBFabc"));
+        assert_eq!(Some(Encoding::Utf16Le), sniff_bom(b"\xFF\xFEa\x00"));
+        assert_eq!(Some(Encoding::Utf16Be), sniff_bom(b"\xFE\xFF\x00a"));
+        assert_eq!(None, sniff_bom(b"abc"));
+    }
+
+    #[test]
+    fn decoding_reader_keeps_chars_split_between_reads() {
+        // Reads one byte at a time, so every code unit and surrogate pair is split
+        struct ByteAtATime<'a>(&'a [u8]);
+        impl Read for ByteAtATime<'_> {
+            fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+                let length = self.0.len().min(buffer.len()).min(1);
+                buffer[..length].copy_from_slice(&self.0[..length]);
+                self.0 = &self.0[length..];
+                Ok(length)
+            }
+        }
+
+        let encoded = utf16le("first 😀\nsecond\n");
+        let mut decoded = String::new();
+        DecodingReader::new(ByteAtATime(&encoded), Encoding::Utf16Le)
+            .read_to_string(&mut decoded)
+            .unwrap();
+
+        assert_eq!("first 😀\nsecond\n", decoded);
+    }
+}
@@ -0,0 +1,110 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::regex::RegexError;
+use crate::walk::WalkError;
+
+// Everything that can go wrong in minigrep. Every error knows the argument, pattern or file
+// it is about, so the message can point right at it.
+#[derive(Debug)]
+pub enum MinigrepError {
+    // An option that does not exist, with the closest one that does if any is close enough
+    UnknownFlag {
+        flag: String,
+        suggestion: Option<&'static str>,
+    },
+    // An option that exists but is used the wrong way, like a missing or bad value
+    BadFlag { flag: String, message: String },
+    MissingPattern,
+    // Arguments that are wrong together, or empty where they can not be
+    BadArguments(&'static str),
+    InvalidRegex { pattern: String, error: RegexError },
+    Io { path: PathBuf, error: io::Error },
+    // Writing the results failed, like when the pipe they go to is closed
+    Output(io::Error),
+}
+
+impl fmt::Display for MinigrepError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownFlag { flag, suggestion } => {
+                write!(f, "Unknown option `{flag}`")?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, ", did you mean `--{suggestion}`?")?;
+                }
+                Ok(())
+            }
+            Self::BadFlag { flag, message } => write!(f, "Option `{flag}` {message}"),
+            Self::MissingPattern => write!(f, "Pattern argument missing (give it as an argument, or with -e or -f)"),
+            Self::BadArguments(message) => write!(f, "{message}"),
+            Self::InvalidRegex { pattern, error } => {
+                // The caret goes under the char the error was found at
+                writeln!(f, "Invalid regular expression: {}", error.message)?;
+                writeln!(f, "    {pattern}")?;
+                write!(f, "    {}^", " ".repeat(error.position))
+            }
+            Self::Io { path, error } => write!(f, "{}: {error}", path.display()),
+            Self::Output(error) => write!(f, "Can not write the output: {error}"),
+        }
+    }
+}
+
+impl Error for MinigrepError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::InvalidRegex { error, .. } => Some(error),
+            Self::Io { error, .. } => Some(error),
+            Self::Output(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<WalkError> for MinigrepError {
+    fn from(error: WalkError) -> Self {
+        Self::Io {
+            path: error.path,
+            error: error.error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_flag_suggests_closest_option() {
+        let error = MinigrepError::UnknownFlag {
+            flag: String::from("--ignore"),
+            suggestion: Some("ignore-case"),
+        };
+
+        assert_eq!("Unknown option `--ignore`, did you mean `--ignore-case`?", error.to_string())
+    }
+
+    #[test]
+    fn invalid_regex_points_at_error_position() {
+        let error = MinigrepError::InvalidRegex {
+            pattern: String::from("a(b))"),
+            error: RegexError::new("Unmatched ')'", 4),
+        };
+
+        assert_eq!(
+            "Invalid regular expression: Unmatched ')'\n    a(b))\n        ^",
+            error.to_string()
+        )
+    }
+
+    #[test]
+    fn io_error_names_the_path() {
+        let error = MinigrepError::Io {
+            path: PathBuf::from("missing.txt"),
+            error: io::Error::from(io::ErrorKind::NotFound),
+        };
+
+        assert!(error.to_string().starts_with("missing.txt: "));
+    }
+}
@@ -0,0 +1,140 @@
+use std::path::Path;
+
+use crate::error::MinigrepError;
+use crate::glob::Glob;
+use crate::types::FileTypes;
+
+// Narrows down the files a recursive search goes through with --glob, -t and -T.
+// Paths are matched from the directory that is searched, so `--glob 'src/*.rs'` works from there.
+//
+// Globs work like the rules of an ignore file the other way around: a glob picks the files to
+// search and a glob starting with ! leaves them out, and the last glob that matches decides.
+// Once there is any glob without a !, a file that none of the globs match is left out.
+// Only the globs with a ! are about directories as well, so `--glob '!vendor'` skips a whole directory.
+//
+// -t only searches files of the given types, and -T leaves out files of the given types.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PathFilter {
+    // Each with true for a glob that picks files, and false for one that leaves them out
+    globs: Vec<(Glob, bool)>,
+    has_picking_globs: bool,
+    selected_types: Vec<Glob>,
+    excluded_types: Vec<Glob>,
+}
+
+impl PathFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_glob(&mut self, glob: &str) {
+        match glob.strip_prefix('!') {
+            Some(glob) => self.globs.push((Glob::new(glob), false)),
+            None => {
+                self.globs.push((Glob::new(glob), true));
+                self.has_picking_globs = true;
+            }
+        }
+    }
+
+    // Searches only the files of the type (for -t), or leaves them out (for -T)
+    pub fn add_type(&mut self, types: &FileTypes, name: &str, selected: bool) -> Result<(), MinigrepError> {
+        let globs = types.globs(name).ok_or_else(|| MinigrepError::BadFlag {
+            flag: String::from(if selected { "--type" } else { "--type-not" }),
+            message: format!("needs a known file type (see --type-list), not `{name}`"),
+        })?;
+
+        let globs = globs.iter().map(|glob| Glob::new(glob));
+        if selected {
+            self.selected_types.extend(globs);
+        } else {
+            self.excluded_types.extend(globs);
+        }
+
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.globs.is_empty() && self.selected_types.is_empty() && self.excluded_types.is_empty()
+    }
+
+    pub fn allows(&self, relative_path: &Path, is_dir: bool) -> bool {
+        let names = Glob::names(relative_path);
+
+        match self.globs.iter().rev().find(|(glob, _)| glob.matches_names(&names)) {
+            Some((_, false)) => return false,
+            None if self.has_picking_globs && !is_dir => return false,
+            _ => {}
+        }
+
+        if is_dir {
+            return true;
+        }
+
+        let is_selected = self.selected_types.is_empty() || self.selected_types.iter().any(|glob| glob.matches_names(&names));
+        is_selected && !self.excluded_types.iter().any(|glob| glob.matches_names(&names))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(globs: &[&str]) -> PathFilter {
+        let mut filter = PathFilter::new();
+        for glob in globs {
+            filter.add_glob(glob);
+        }
+        filter
+    }
+
+    #[test]
+    fn path_filter_picks_and_leaves_out_files_by_glob() {
+        let filter = filter(&["*.rs", "!*_test.rs"]);
+
+        assert!(filter.allows(Path::new("src/main.rs"), false));
+        assert!(!filter.allows(Path::new("src/parser_test.rs"), false));
+        assert!(!filter.allows(Path::new("README.md"), false));
+        // Directories are still entered to look for the files the globs pick
+        assert!(filter.allows(Path::new("src"), true));
+    }
+
+    #[test]
+    fn path_filter_last_matching_glob_wins() {
+        let filter = filter(&["!*.rs", "main.rs"]);
+
+        assert!(filter.allows(Path::new("src/main.rs"), false));
+        assert!(!filter.allows(Path::new("src/lib.rs"), false));
+    }
+
+    #[test]
+    fn path_filter_leaves_out_directories_with_negated_glob() {
+        let filter = filter(&["!vendor"]);
+
+        assert!(!filter.allows(Path::new("vendor"), true));
+        assert!(filter.allows(Path::new("src/lib.rs"), false));
+    }
+
+    #[test]
+    fn path_filter_selects_and_excludes_file_types() {
+        let mut types = FileTypes::new();
+        types.add("proto:*.proto").unwrap();
+        let mut filter = PathFilter::new();
+        filter.add_type(&types, "rust", true).unwrap();
+        filter.add_type(&types, "proto", true).unwrap();
+
+        assert!(filter.allows(Path::new("Cargo.toml"), false));
+        assert!(filter.allows(Path::new("api/user.proto"), false));
+        assert!(!filter.allows(Path::new("index.js"), false));
+
+        let mut filter = PathFilter::new();
+        filter.add_type(&types, "js", false).unwrap();
+        assert!(!filter.allows(Path::new("web/index.js"), false));
+        assert!(filter.allows(Path::new("src/lib.rs"), false));
+    }
+
+    #[test]
+    fn path_filter_fails_on_unknown_type() {
+        assert!(PathFilter::new().add_type(&FileTypes::new(), "klingon", true).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use std::path::{Component, Path};
+
+// A glob in the syntax of .gitignore files, which --glob and the file types use as well:
+//
+// - A glob with a / at the start or in the middle is anchored: it is matched against the whole path.
+//   Any other glob matches a file or directory name at any depth, so *.rs matches src/main.rs.
+// - * and ? match anything but a /, [a-z] and [!a-z] match one char of a set, and \ escapes a char
+// - ** matches any number of directories (as in **/logs, logs/** and a/**/b)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glob {
+    segments: Vec<Segment>,
+}
+
+// A part of a glob between two slashes
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    // ** matches any number of directories
+    AnyDirs,
+    Name(Vec<char>),
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Self {
+        let anchored = pattern.contains('/');
+
+        let mut segments = Vec::new();
+        if !anchored {
+            segments.push(Segment::AnyDirs);
+        }
+        for part in pattern.split('/').filter(|part| !part.is_empty()) {
+            segments.push(match part {
+                "**" => Segment::AnyDirs,
+                _ => Segment::Name(part.chars().collect()),
+            });
+        }
+
+        Self { segments }
+    }
+
+    pub fn is_match(&self, path: &Path) -> bool {
+        self.matches_names(&Self::names(path))
+    }
+
+    // The names in a path, which is what a glob is matched against. When a path is checked against
+    // many globs, the names only have to be split up once.
+    pub fn names(path: &Path) -> Vec<String> {
+        path.components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn matches_names(&self, names: &[String]) -> bool {
+        match_segments(&self.segments, names)
+    }
+}
+
+fn match_segments(segments: &[Segment], names: &[String]) -> bool {
+    match segments.split_first() {
+        None => names.is_empty(),
+        // A trailing ** matches everything inside a directory, but not the directory itself
+        Some((Segment::AnyDirs, [])) => !names.is_empty(),
+        Some((Segment::AnyDirs, rest)) => (0..=names.len()).any(|skip| match_segments(rest, &names[skip..])),
+        Some((Segment::Name(glob), rest)) => names
+            .split_first()
+            .is_some_and(|(name, names)| match_glob(glob, &name.chars().collect::<Vec<char>>()) && match_segments(rest, names)),
+    }
+}
+
+// Matches a single file or directory name, so * never has to care about slashes
+fn match_glob(glob: &[char], name: &[char]) -> bool {
+    match glob.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| match_glob(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && match_glob(rest, &name[1..]),
+        Some(('[', rest)) => match (name.split_first(), match_class(rest)) {
+            (Some((c, name)), Some((matches, rest))) => matches(*c) && match_glob(rest, name),
+            // A [ without a closing ] is a plain [
+            (Some(('[', name)), None) => match_glob(rest, name),
+            _ => false,
+        },
+        Some(('\\', [escaped, rest @ ..])) => name.first() == Some(escaped) && match_glob(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && match_glob(rest, &name[1..]),
+    }
+}
+
+// Parses a class like [a-z] or [!0-9] (after the [) into a test for a char and the rest of the glob
+fn match_class(glob: &[char]) -> Option<(impl Fn(char) -> bool, &[char])> {
+    let (negated, glob) = match glob.first() {
+        Some('!' | '^') => (true, &glob[1..]),
+        _ => (false, glob),
+    };
+
+    // A ] right at the start is part of the set
+    let end = glob.iter().skip(1).position(|&c| c == ']')? + 1;
+    let set = glob[..end].to_vec();
+
+    let matches = move |c: char| {
+        let mut index = 0;
+        let mut found = false;
+        while index < set.len() {
+            if index + 2 < set.len() && set[index + 1] == '-' {
+                found |= set[index] <= c && c <= set[index + 2];
+                index += 3;
+            } else {
+                found |= set[index] == c;
+                index += 1;
+            }
+        }
+        found != negated
+    };
+
+    Some((matches, &glob[end + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(glob: &str, path: &str) -> bool {
+        Glob::new(glob).is_match(Path::new(path))
+    }
+
+    #[test]
+    fn glob_without_slash_matches_names_at_any_depth() {
+        assert!(is_match("*.rs", "main.rs"));
+        assert!(is_match("*.rs", "src/regex/dfa.rs"));
+        assert!(is_match("Makefile", "build/Makefile"));
+        assert!(!is_match("*.rs", "src/main.rs.orig"));
+    }
+
+    #[test]
+    fn glob_with_slash_matches_whole_path() {
+        assert!(is_match("src/*.rs", "src/main.rs"));
+        assert!(!is_match("src/*.rs", "src/regex/dfa.rs"));
+        assert!(is_match("src/**/*.rs", "src/regex/dfa.rs"));
+        assert!(is_match("/docs", "docs"));
+    }
+
+    #[test]
+    fn glob_star_does_not_cross_directories() {
+        assert!(is_match("a*c", "abbc"));
+        assert!(!is_match("a*c/d", "ab/c/d"));
+        assert!(is_match("*_test.rs", "tests/parser_test.rs"));
+    }
+
+    #[test]
+    fn glob_handles_classes_and_escapes() {
+        assert!(is_match("[a-c]?.txt", "b1.txt"));
+        assert!(!is_match("[!a-c]?.txt", "b1.txt"));
+        assert!(is_match("\\*.txt", "*.txt"));
+        assert!(!is_match("\\*.txt", "a.txt"));
+        assert!(is_match("[.txt", "[.txt"));
+    }
+}
@@ -0,0 +1,256 @@
+// A gzip decoder (RFC 1952) without any external crates, so compressed logs can be searched
+// like any other input. A gzip file is one or more members, each a header, DEFLATE compressed
+// data and a trailer with the CRC-32 and the length of the data. Rotated logs are often several
+// members glued together, which decode to the texts of all of them one after the other.
+mod inflate;
+
+use std::io::{self, BufRead, Read};
+
+use inflate::{BitReader, Inflate, WINDOW_SIZE, invalid_data};
+
+// Every gzip member starts with these two bytes
+pub const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+const DEFLATE_METHOD: u8 = 8;
+
+// Header flags that say which optional fields follow
+const FLAG_HEADER_CRC: u8 = 0x02;
+const FLAG_EXTRA: u8 = 0x04;
+const FLAG_NAME: u8 = 0x08;
+const FLAG_COMMENT: u8 = 0x10;
+
+// How much is decoded at once, on top of the window that is kept for back references
+const DECODE_BLOCK_SIZE: usize = 64 * 1024;
+
+pub fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&GZIP_MAGIC)
+}
+
+// Decompresses a gzip stream as it is read, so a compressed log is searched without ever being in
+// memory as a whole. Broken data is an InvalidData error, and data that ends too early UnexpectedEof.
+pub struct GzipReader<R> {
+    bits: BitReader<R>,
+    inflate: Inflate,
+    in_member: bool,
+    finished: bool,
+    // The decoded bytes, the last WINDOW_SIZE of which are kept after they are read
+    output: Vec<u8>,
+    output_position: usize,
+    // Of the data of the current member so far, checked against the trailer
+    crc: u32,
+    size: u32,
+}
+
+impl<R: BufRead> GzipReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            bits: BitReader::new(reader),
+            inflate: Inflate::new(),
+            in_member: false,
+            finished: false,
+            output: Vec::new(),
+            output_position: 0,
+            crc: 0,
+            size: 0,
+        }
+    }
+
+    fn read_header(&mut self) -> io::Result<()> {
+        let mut header = [0; 10];
+        for byte in &mut header {
+            *byte = self.bits.byte()?;
+        }
+        if header[..2] != GZIP_MAGIC {
+            return Err(invalid_data("not a gzip member"));
+        }
+        if header[2] != DEFLATE_METHOD {
+            return Err(invalid_data("unknown compression method"));
+        }
+
+        // The modification time, the flags about the compression and the OS in the rest of the
+        // header, and the optional fields, are not needed to decompress
+        let flags = header[3];
+        if flags & FLAG_EXTRA != 0 {
+            let length = u16::from_le_bytes([self.bits.byte()?, self.bits.byte()?]);
+            for _ in 0..length {
+                self.bits.byte()?;
+            }
+        }
+        for flag in [FLAG_NAME, FLAG_COMMENT] {
+            if flags & flag != 0 {
+                while self.bits.byte()? != 0 {}
+            }
+        }
+        if flags & FLAG_HEADER_CRC != 0 {
+            self.bits.byte()?;
+            self.bits.byte()?;
+        }
+
+        self.inflate = Inflate::new();
+        self.crc = 0;
+        self.size = 0;
+        // A member can not refer back into the one before it
+        self.output.drain(..self.output_position);
+        self.output_position = 0;
+        self.in_member = true;
+        Ok(())
+    }
+
+    fn read_trailer(&mut self) -> io::Result<()> {
+        self.bits.align_to_byte();
+        let mut trailer = [0; 8];
+        for byte in &mut trailer {
+            *byte = self.bits.byte()?;
+        }
+
+        let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+        if crc != self.crc {
+            return Err(invalid_data("CRC-32 does not match the data"));
+        }
+        // The size is only kept modulo 2^32, since it is only 4 bytes
+        if size != self.size {
+            return Err(invalid_data("length does not match the data"));
+        }
+
+        // Anything after a member that is not another member, like the zero padding dd and tape
+        // drives leave, is ignored like gzip does, since all the data before it decoded fine
+        self.in_member = false;
+        self.finished = !self.bits.peek_bytes(&GZIP_MAGIC)?;
+        Ok(())
+    }
+
+    fn decode_block(&mut self) -> io::Result<()> {
+        if !self.in_member {
+            return self.read_header();
+        }
+
+        // The bytes that are read already are only kept as far as back references can reach
+        let unneeded = self.output_position.min(self.output.len().saturating_sub(WINDOW_SIZE));
+        self.output.drain(..unneeded);
+        self.output_position -= unneeded;
+
+        let start = self.output.len();
+        self.inflate.decode(&mut self.bits, &mut self.output, DECODE_BLOCK_SIZE)?;
+        let decoded = &self.output[start..];
+        self.crc = crc32_update(self.crc, decoded);
+        self.size = self.size.wrapping_add(decoded.len() as u32);
+
+        if self.inflate.is_done() {
+            self.read_trailer()?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for GzipReader<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        while self.output_position == self.output.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            self.decode_block()?;
+        }
+
+        let decoded = &self.output[self.output_position..];
+        let length = decoded.len().min(buffer.len());
+        buffer[..length].copy_from_slice(&decoded[..length]);
+        self.output_position += length;
+
+        Ok(length)
+    }
+}
+
+// The CRC-32 that gzip uses (the same as zip and PNG), a byte at a time with a table of the
+// 256 possible remainders, which is built at compile time
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+};
+
+fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in bytes {
+        crc = CRC32_TABLE[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // poem.txt compressed with gzip -9, which uses dynamic Huffman codes
+    const POEM: &[u8] = include_bytes!("../fixtures/poem.txt.gz");
+    // Two members, the first stored without compression, with a name, a comment, an extra field
+    // and a header CRC, and the second with fixed Huffman codes
+    const MEMBERS: &[u8] = include_bytes!("../fixtures/members.gz");
+    // A log of more than 64 KiB, so back references go across the blocks the decoder works in
+    const LARGE: &[u8] = include_bytes!("../fixtures/large.log.gz");
+
+    fn gunzip(compressed: &[u8]) -> io::Result<Vec<u8>> {
+        let mut decoded = Vec::new();
+        GzipReader::new(compressed).read_to_end(&mut decoded)?;
+        Ok(decoded)
+    }
+
+    #[test]
+    fn gzip_reader_decodes_dynamic_huffman_codes() {
+        assert_eq!(include_bytes!("../poem.txt").as_slice(), gunzip(POEM).unwrap());
+    }
+
+    #[test]
+    fn gzip_reader_decodes_every_member() {
+        assert_eq!(b"stored member\nfixed member, fixed member, fixed member\n".as_slice(), gunzip(MEMBERS).unwrap());
+    }
+
+    #[test]
+    fn gzip_reader_ignores_trailing_garbage_after_member() {
+        let poem = include_bytes!("../poem.txt").as_slice();
+        for garbage in [&[0u8; 512][..], b"\x1f", b"\x1f\x8c not a member"] {
+            let padded = [POEM, garbage].concat();
+            assert_eq!(poem, gunzip(&padded).unwrap());
+        }
+
+        // Another member after the first one is still decoded, and has to be whole
+        let twice = [POEM, POEM].concat();
+        assert_eq!([poem, poem].concat(), gunzip(&twice).unwrap());
+        assert!(gunzip(&twice[..POEM.len() + 4]).is_err());
+    }
+
+    #[test]
+    fn gzip_reader_keeps_window_between_blocks() {
+        let decoded = gunzip(LARGE).unwrap();
+        let text = String::from_utf8(decoded).unwrap();
+
+        assert_eq!(2000, text.lines().count());
+        assert!(text.lines().enumerate().all(|(index, line)| line.starts_with(&format!("{} ", index + 1))));
+    }
+
+    #[test]
+    fn gzip_reader_fails_on_corrupt_data() {
+        let mut corrupt = POEM.to_vec();
+        let last = corrupt.len() - 5;
+        corrupt[last] ^= 0xFF; // In the CRC-32 of the trailer
+
+        assert_eq!(io::ErrorKind::InvalidData, gunzip(&corrupt).unwrap_err().kind());
+        assert_eq!(io::ErrorKind::UnexpectedEof, gunzip(&POEM[..POEM.len() / 2]).unwrap_err().kind());
+        assert_eq!(io::ErrorKind::InvalidData, gunzip(b"\x1f\x8b\x07\x00\x00\x00\x00\x00\x00\x03").unwrap_err().kind());
+    }
+
+    #[test]
+    fn crc32_matches_known_value() {
+        assert_eq!(0xCBF4_3926, crc32_update(0, b"123456789"));
+    }
+}
@@ -0,0 +1,451 @@
+use std::io::{self, BufRead};
+
+// A DEFLATE decoder (RFC 1951), the compression inside gzip.
+//
+// A DEFLATE stream is a series of blocks. A block is either stored as it is, or Huffman coded with
+// fixed or with its own (dynamic) code tables. Huffman coded blocks are made of literal bytes and
+// back references, which copy a run of up to 258 bytes from up to 32 KiB back in the output.
+// The decoder works a part of a block at a time, so the output never has to be in memory as a whole.
+
+// How far back a back reference can reach, so how much of the output has to be kept around
+pub const WINDOW_SIZE: usize = 32 * 1024;
+
+const MAX_CODE_LENGTH: usize = 15;
+// Codes up to this many bits are decoded with a single table lookup, longer ones bit by bit
+const FAST_BITS: u32 = 9;
+
+// The lengths and distances of back references are a base plus some extra bits
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097,
+    6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+// The order the code lengths of the code length code come in, in a dynamic block header
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+const END_OF_BLOCK: u16 = 256;
+
+pub fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid gzip data: {message}"))
+}
+
+// DEFLATE packs its values starting at the lowest bit of each byte. Up to 8 bytes are read ahead
+// into a u64, so most values can be taken out without going back to the reader.
+pub struct BitReader<R> {
+    reader: R,
+    bits: u64,
+    bit_count: u32,
+}
+
+impl<R: BufRead> BitReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            bits: 0,
+            bit_count: 0,
+        }
+    }
+
+    // Reads ahead as far as the u64 allows. At the end of the input there are just fewer bits.
+    fn refill(&mut self) -> io::Result<()> {
+        while self.bit_count <= 56 {
+            let available = self.reader.fill_buf()?;
+            if available.is_empty() {
+                break;
+            }
+
+            let taken = available.len().min(((64 - self.bit_count) / 8) as usize);
+            for &byte in &available[..taken] {
+                self.bits |= u64::from(byte) << self.bit_count;
+                self.bit_count += 8;
+            }
+            self.reader.consume(taken);
+        }
+
+        Ok(())
+    }
+
+    pub fn bits(&mut self, count: u32) -> io::Result<u32> {
+        if count == 0 {
+            return Ok(0);
+        }
+        if self.bit_count < count {
+            self.refill()?;
+            if self.bit_count < count {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "gzip data ends too early"));
+            }
+        }
+
+        let value = (self.bits & ((1 << count) - 1)) as u32;
+        self.bits >>= count;
+        self.bit_count -= count;
+        Ok(value)
+    }
+
+    pub fn byte(&mut self) -> io::Result<u8> {
+        Ok(self.bits(8)? as u8)
+    }
+
+    // Stored blocks and everything after the compressed data start at a whole byte
+    pub fn align_to_byte(&mut self) {
+        let partial = self.bit_count % 8;
+        self.bits >>= partial;
+        self.bit_count -= partial;
+    }
+
+    // Checks if the input goes on with these bytes, like the start of another gzip member after the
+    // one that just ended, without reading past them. Has to be at a whole byte, and there can be
+    // no more bytes than the u64 holds after a refill.
+    pub fn peek_bytes(&mut self, bytes: &[u8]) -> io::Result<bool> {
+        self.refill()?;
+        let count = bytes.len() as u32 * 8;
+        if self.bit_count < count {
+            return Ok(false);
+        }
+
+        let peeked = self.bits.to_le_bytes();
+        Ok(peeked[..bytes.len()] == *bytes)
+    }
+}
+
+// A canonical Huffman code, where the codes follow from their lengths alone
+struct Huffman {
+    // The number of codes of every length
+    counts: [u16; MAX_CODE_LENGTH + 1],
+    // The symbols ordered by code, which is by code length and then by symbol
+    symbols: Vec<u16>,
+    // For the short codes, indexed by the next FAST_BITS bits of the input: the symbol and the code
+    // length packed as symbol << 4 | length, or 0 when the code is longer
+    fast: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Self> {
+        let mut counts = [0u16; MAX_CODE_LENGTH + 1];
+        for &length in lengths {
+            counts[usize::from(length)] += 1;
+        }
+        counts[0] = 0;
+
+        // More codes of a length than there is room for can not be decoded. A code with room to spare
+        // is allowed though, like a single distance code, and a symbol that is not there is an error.
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                return Err(invalid_data("Huffman code has too many codes"));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_CODE_LENGTH + 2];
+        for length in 1..=MAX_CODE_LENGTH {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; usize::from(offsets[MAX_CODE_LENGTH + 1])];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[usize::from(offsets[usize::from(length)])] = symbol as u16;
+                offsets[usize::from(length)] += 1;
+            }
+        }
+
+        // The codes are counted up in order, and since they come in the input starting at their
+        // highest bit, they are reversed to look them up by the bits as they come
+        let mut fast = vec![0u16; 1 << FAST_BITS];
+        let mut code: u32 = 0;
+        let mut index = 0;
+        for length in 1..=MAX_CODE_LENGTH as u32 {
+            for _ in 0..counts[length as usize] {
+                if length <= FAST_BITS {
+                    let reversed = code.reverse_bits() >> (32 - length);
+                    let entry = (symbols[index] << 4) | length as u16;
+                    for slot in (reversed as usize..fast.len()).step_by(1 << length) {
+                        fast[slot] = entry;
+                    }
+                }
+                code += 1;
+                index += 1;
+            }
+            code <<= 1;
+        }
+
+        Ok(Self { counts, symbols, fast })
+    }
+
+    fn decode<R: BufRead>(&self, bits: &mut BitReader<R>) -> io::Result<u16> {
+        if bits.bit_count < FAST_BITS {
+            bits.refill()?;
+        }
+
+        let entry = self.fast[(bits.bits & ((1 << FAST_BITS) - 1)) as usize];
+        let length = u32::from(entry & 0xF);
+        if entry != 0 && length <= bits.bit_count {
+            bits.bits >>= length;
+            bits.bit_count -= length;
+            return Ok(entry >> 4);
+        }
+
+        // A long code, or the input ends soon: the code is read one bit at a time. The first code of
+        // every length is worked out from the counts, so it is known which codes that length has.
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for &count in &self.counts[1..] {
+            code |= bits.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(invalid_data("unknown Huffman code"))
+    }
+}
+
+enum State {
+    // At the header of the next block
+    BlockStart,
+    // In a stored block, with the number of bytes left in it
+    Stored(usize),
+    // In a Huffman coded block, with the codes for literals and lengths, and for distances
+    Huffman(Box<(Huffman, Huffman)>),
+    Done,
+}
+
+pub struct Inflate {
+    state: State,
+    last_block: bool,
+}
+
+impl Inflate {
+    pub fn new() -> Self {
+        Self {
+            state: State::BlockStart,
+            last_block: false,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, State::Done)
+    }
+
+    // Decodes onto the end of the output until it has grown by at least wanted bytes, or the stream ends.
+    // The output has to hold the last WINDOW_SIZE bytes decoded before, for the back references.
+    pub fn decode<R: BufRead>(&mut self, bits: &mut BitReader<R>, output: &mut Vec<u8>, wanted: usize) -> io::Result<()> {
+        let target = output.len() + wanted;
+
+        while output.len() < target {
+            match &mut self.state {
+                State::Done => break,
+                State::BlockStart => {
+                    if self.last_block {
+                        self.state = State::Done;
+                        break;
+                    }
+                    self.state = self.read_block_header(bits)?;
+                }
+                State::Stored(left) => {
+                    let count = (*left).min(target - output.len());
+                    for _ in 0..count {
+                        output.push(bits.byte()?);
+                    }
+                    *left -= count;
+                    if *left == 0 {
+                        self.state = State::BlockStart;
+                    }
+                }
+                State::Huffman(codes) => {
+                    let (literals, distances) = &**codes;
+                    if decode_symbols(bits, literals, distances, output, target)? {
+                        self.state = State::BlockStart;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_block_header<R: BufRead>(&mut self, bits: &mut BitReader<R>) -> io::Result<State> {
+        self.last_block = bits.bits(1)? == 1;
+
+        match bits.bits(2)? {
+            0 => {
+                bits.align_to_byte();
+                let length = bits.bits(16)?;
+                let inverted_length = bits.bits(16)?;
+                if length != !inverted_length & 0xFFFF {
+                    return Err(invalid_data("stored block length does not match its check"));
+                }
+                Ok(State::Stored(length as usize))
+            }
+            1 => Ok(State::Huffman(Box::new(fixed_codes()?))),
+            2 => Ok(State::Huffman(Box::new(read_dynamic_codes(bits)?))),
+            _ => Err(invalid_data("unknown block type")),
+        }
+    }
+}
+
+impl Default for Inflate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Decodes symbols until the end of the block, which returns true, or until the output reaches target
+fn decode_symbols<R: BufRead>(
+    bits: &mut BitReader<R>,
+    literals: &Huffman,
+    distances: &Huffman,
+    output: &mut Vec<u8>,
+    target: usize,
+) -> io::Result<bool> {
+    while output.len() < target {
+        let symbol = literals.decode(bits)?;
+
+        if symbol < END_OF_BLOCK {
+            output.push(symbol as u8);
+            continue;
+        }
+        if symbol == END_OF_BLOCK {
+            return Ok(true);
+        }
+
+        let index = usize::from(symbol - 257);
+        if index >= LENGTH_BASE.len() {
+            return Err(invalid_data("unknown length code"));
+        }
+        let length = usize::from(LENGTH_BASE[index]) + bits.bits(u32::from(LENGTH_EXTRA[index]))? as usize;
+
+        let index = usize::from(distances.decode(bits)?);
+        if index >= DISTANCE_BASE.len() {
+            return Err(invalid_data("unknown distance code"));
+        }
+        let distance = usize::from(DISTANCE_BASE[index]) + bits.bits(u32::from(DISTANCE_EXTRA[index]))? as usize;
+        if distance > output.len() {
+            return Err(invalid_data("back reference reaches before the start"));
+        }
+
+        // The run can overlap the bytes it is copied to, like a distance of 1 repeating one byte,
+        // so it is copied a byte at a time
+        let start = output.len() - distance;
+        for offset in 0..length {
+            let byte = output[start + offset];
+            output.push(byte);
+        }
+    }
+
+    Ok(false)
+}
+
+// The codes of a block with fixed Huffman codes, which are set by the standard
+fn fixed_codes() -> io::Result<(Huffman, Huffman)> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+// A dynamic block starts with its code lengths, which are Huffman coded themselves
+fn read_dynamic_codes<R: BufRead>(bits: &mut BitReader<R>) -> io::Result<(Huffman, Huffman)> {
+    let literal_count = bits.bits(5)? as usize + 257;
+    let distance_count = bits.bits(5)? as usize + 1;
+    let code_length_count = bits.bits(4)? as usize + 4;
+
+    let mut code_length_lengths = [0u8; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_length_lengths[symbol] = bits.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_length_lengths)?;
+
+    // The lengths of both codes come as one list, where runs of the same length are shortened
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut index = 0;
+    while index < lengths.len() {
+        let symbol = code_lengths.decode(bits)?;
+        let (length, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths[..index]
+                    .last()
+                    .ok_or_else(|| invalid_data("length repeat without a length before it"))?;
+                (previous, 3 + bits.bits(2)? as usize)
+            }
+            17 => (0, 3 + bits.bits(3)? as usize),
+            _ => (0, 11 + bits.bits(7)? as usize),
+        };
+
+        if index + repeat > lengths.len() {
+            return Err(invalid_data("too many code lengths"));
+        }
+        lengths[index..index + repeat].fill(length);
+        index += repeat;
+    }
+
+    if lengths[usize::from(END_OF_BLOCK)] == 0 {
+        return Err(invalid_data("block has no end code"));
+    }
+
+    let (literal_lengths, distance_lengths) = lengths.split_at(literal_count);
+    Ok((Huffman::new(literal_lengths)?, Huffman::new(distance_lengths)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inflate(compressed: &[u8]) -> io::Result<Vec<u8>> {
+        let mut bits = BitReader::new(compressed);
+        let mut inflate = Inflate::new();
+        let mut output = Vec::new();
+        while !inflate.is_done() {
+            inflate.decode(&mut bits, &mut output, 7)?;
+        }
+        Ok(output)
+    }
+
+    #[test]
+    fn inflate_decodes_stored_block() {
+        assert_eq!(b"hello", &inflate(b"\x01\x05\x00\xfa\xffhello").unwrap()[..]);
+    }
+
+    #[test]
+    fn inflate_decodes_fixed_huffman_block_with_back_references() {
+        // "abcabcabcabc\n", compressed by zlib with fixed codes
+        let compressed = b"\x4b\x4c\x4a\x4e\x84\x21\x2e\x00";
+
+        assert_eq!(b"abcabcabcabc\n", &inflate(compressed).unwrap()[..]);
+    }
+
+    #[test]
+    fn inflate_fails_on_bad_data() {
+        assert_eq!(io::ErrorKind::InvalidData, inflate(b"\x07").unwrap_err().kind());
+        assert_eq!(io::ErrorKind::InvalidData, inflate(b"\x01\x05\x00\x00\x00hello").unwrap_err().kind());
+        assert_eq!(io::ErrorKind::UnexpectedEof, inflate(b"\x01\x05\x00\xfa\xffhel").unwrap_err().kind());
+    }
+
+    #[test]
+    fn bit_reader_reads_bits_from_lowest_bit_first() {
+        let mut bits = BitReader::new(&b"\xb5\x01\x02"[..]);
+
+        assert_eq!(0b101, bits.bits(3).unwrap());
+        assert_eq!(0b10110, bits.bits(5).unwrap());
+        assert!(bits.peek_bytes(b"\x01\x02").unwrap());
+        assert!(!bits.peek_bytes(b"\x01\x02\x03").unwrap());
+        assert_eq!(1, bits.byte().unwrap());
+        assert_eq!(2, bits.byte().unwrap());
+        assert!(!bits.peek_bytes(b"\x00").unwrap());
+    }
+}
@@ -1,75 +1,31 @@
-use crate::model::{Content, Line};
-
-impl Content {
-    pub fn highlight(&self, query: &str, ignore_case: bool) -> Self {
-        let mut highlighted_content = Self::new();
-
-        for line in &self.lines {
-            let highlighted_text = line.highlight(query, ignore_case);
-            highlighted_content
-                .lines
-                .push(Line::new(line.index, highlighted_text));
-        }
-
-        highlighted_content
-    }
-}
+use crate::model::Line;
 
 impl Line {
-    pub fn highlight(&self, query: &str, ignore_case: bool) -> String {
+    // Wraps the matches that the search recorded in color codes. This is only done right before the
+    // line is printed, so the line itself always keeps its plain text. The bytes that are not valid
+    // UTF-8 are shown as U+FFFD, one piece at a time, so a match always gets the colors it should.
+    pub fn highlight(&self) -> String {
         let mut highlighted_text = String::new();
 
-        // If ignore case is enabled, we want to compare in lowercase but still keep the normal case chars for display. Hence the duplicate vecs
-        let text_chars: Vec<char> = self.text.chars().collect();
-        let case_aware_text_chars: Vec<char> = if ignore_case {
-            self.text.to_lowercase().chars().collect()
-        } else {
-            self.text.chars().collect()
-        };
+        // The text between the matches is copied as is and the matches are wrapped in color codes
         let mut text_index = 0;
 
-        // Same here, we want to compare in lowercase but display normal case
-        let query_chars: Vec<char> = query.chars().collect();
-        let case_aware_query_chars: Vec<char> = if ignore_case {
-            query.to_lowercase().chars().collect()
-        } else {
-            query.chars().collect()
-        };
-
-        // matching_query_index represent the number of previous consecutive matches in chars for between the content and query
-        // So if matching_query_index is for example 3, that means that the past three chars in text_chars have been the same as the first 3 chars in query_chars
-        let mut matching_query_index = 0;
-
-        // Map through each char in the line text
-        // For each char, check if the matching_query_index (see explination above) is the the same as the length of the query
-        // If so, push the query with color to the highlighted_text and reset matching_query_index
-        // Else, push the char without color
-        // Then, check if the current char is the same as the query char for the current matching_query_index
-        // If so, incriment it. Else reset it to 0
-        // Finally, incriment text_index to move on to the next char
-        while text_index < text_chars.len() {
-            if matching_query_index == query_chars.len() {
-                // "\x1b[31m" means start of red color section
-                highlighted_text
-                    .insert_str(highlighted_text.len() - matching_query_index, "\x1b[31m");
-
-                // "\x1b[0m" means end of red color section
-                highlighted_text.push_str("\x1b[0m");
-
-                matching_query_index = 0;
-            }
-
-            highlighted_text.push(text_chars[text_index]);
-
-            if case_aware_text_chars[text_index] == case_aware_query_chars[matching_query_index] {
-                matching_query_index += 1;
-            } else {
-                matching_query_index = 0;
-            }
-
-            text_index += 1;
+        for found_match in &self.matches {
+            let (match_start, match_end) = (found_match.byte_range.start, found_match.byte_range.end);
+
+            highlighted_text.push_str(&String::from_utf8_lossy(&self.text[text_index..match_start]));
+
+            // "\x1b[31m" means start of red color section
+            highlighted_text.push_str("\x1b[31m");
+            highlighted_text.push_str(&String::from_utf8_lossy(&self.text[match_start..match_end]));
+            // "\x1b[0m" means end of red color section
+            highlighted_text.push_str("\x1b[0m");
+
+            text_index = match_end;
         }
 
+        highlighted_text.push_str(&String::from_utf8_lossy(&self.text[text_index..]));
+
         highlighted_text
     }
 }
@@ -77,30 +33,42 @@ impl Line {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::matcher::Matcher;
+
+    fn matcher(query: &str, ignore_case: bool) -> Matcher {
+        Matcher::new(&[query], ignore_case, false, false).unwrap()
+    }
+
+    // Runs the search on a line, so it has its matches recorded like when it is printed
+    fn searched_line(text: &str, matcher: &Matcher) -> Line {
+        let mut line = Line::new(1, String::from(text));
+        line.select(matcher, false);
+        line
+    }
 
     #[test]
     fn line_highlight_wraps_matching_query_in_red() {
-        let line = Line::new(1, String::from("I am a line."));
+        let line = searched_line("I am a line.", &matcher("line", false));
 
-        let highlighted_text = line.highlight("line", false);
+        let highlighted_text = line.highlight();
 
         assert_eq!("I am a \x1b[31mline\x1b[0m.", highlighted_text)
     }
 
     #[test]
     fn no_line_highlight_when_query_not_found() {
-        let line = Line::new(1, String::from("I am a line."));
+        let line = searched_line("I am a line.", &matcher("monkey", false));
 
-        let highlighted_text = line.highlight("monkey", false);
+        let highlighted_text = line.highlight();
 
-        assert_eq!(line.text, highlighted_text)
+        assert_eq!(line.text_lossy(), highlighted_text)
     }
 
     #[test]
     fn line_highlight_ignores_case_when_enabled() {
-        let line = Line::new(1, String::from("To be or not to be, that is the question"));
+        let line = searched_line("To be or not to be, that is the question", &matcher("to", true));
 
-        let highlighted_text = line.highlight("to", true);
+        let highlighted_text = line.highlight();
 
         assert_eq!(
             "\x1b[31mTo\x1b[0m be or not \x1b[31mto\x1b[0m be, that is the question",
@@ -110,9 +78,9 @@ mod tests {
 
     #[test]
     fn line_highlight_does_not_ignore_case_when_disabled() {
-        let line = Line::new(1, String::from("To be or not to be, that is the question"));
+        let line = searched_line("To be or not to be, that is the question", &matcher("to", false));
 
-        let highlighted_text = line.highlight("to", false);
+        let highlighted_text = line.highlight();
 
         assert_eq!(
             "To be or not \x1b[31mto\x1b[0m be, that is the question",
@@ -121,24 +89,43 @@ mod tests {
     }
 
     #[test]
-    fn text_highlight_wraps_matching_query_in_red_in_lines() {
-        let content = Content::from_lines(vec![
-            Line::new(1, String::from("I am a line.")),
-            Line::new(2, String::from("I am also line.")),
-            Line::new(3, String::from("Me too!")),
-            Line::new(3, String::from("Line")),
-        ]);
+    fn line_highlight_leaves_lines_without_recorded_matches_alone() {
+        // Context lines are never searched for matches, so they are printed as they are
+        let line = Line::context(3, String::from("I am a line."));
+
+        let highlighted_text = line.highlight();
+
+        assert_eq!("I am a line.", highlighted_text)
+    }
+
+    #[test]
+    fn line_highlight_wraps_match_at_end_of_line() {
+        let line = searched_line("Me too", &matcher("too", false));
 
-        let highlighted_content = content.highlight("line", false);
+        let highlighted_text = line.highlight();
+
+        assert_eq!("Me \x1b[31mtoo\x1b[0m", highlighted_text)
+    }
+
+    #[test]
+    fn line_highlight_wraps_every_regex_match() {
+        let matcher = Matcher::new(&[r"\d+"], false, false, true).unwrap();
+        let line = searched_line("error 1, warning 22", &matcher);
+
+        let highlighted_text = line.highlight();
 
         assert_eq!(
-            Content::from_lines(vec![
-                Line::new(1, String::from("I am a \x1b[31mline\x1b[0m.")),
-                Line::new(2, String::from("I am also \x1b[31mline\x1b[0m.")),
-                Line::new(3, String::from("Me too!")),
-                Line::new(3, String::from("Line")),
-            ]),
-            highlighted_content
-        )
+            "error \x1b[31m1\x1b[0m, warning \x1b[31m22\x1b[0m",
+            highlighted_text
+        );
+        assert_eq!(b"error 1, warning 22", &line.text[..]);
+    }
+
+    #[test]
+    fn line_highlight_shows_invalid_utf8_as_replacement_chars() {
+        let mut line = Line::new(1, &b"caf\xe9 error\xff"[..]);
+        line.select(&matcher("error", false), false);
+
+        assert_eq!("caf\u{FFFD} \x1b[31merror\x1b[0m\u{FFFD}", line.highlight())
     }
 }
@@ -0,0 +1,161 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::glob::Glob;
+
+// The rules of one .gitignore (or .ignore, or .git/info/exclude) file, in gitignore syntax:
+//
+// - Blank lines and lines starting with # are skipped, \# and \! stand for a plain # and !
+// - A rule starting with ! re-includes what an earlier rule ignored
+// - A rule ending with / only matches directories
+// - The rest of the rule is a Glob, matched against the path from the directory the file is in
+//
+// Like in git, the last rule that matches a path decides whether it is ignored.
+#[derive(Debug, Clone, Default)]
+pub struct Gitignore {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    glob: Glob,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl Gitignore {
+    pub fn parse(text: &str) -> Self {
+        Self {
+            rules: text.lines().filter_map(Rule::parse).collect(),
+        }
+    }
+
+    // A file that does not exist has no rules, which is the common case for most directories
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(Self::parse(&text)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // Decides about a path relative to the directory the rules are from. Some(true) means ignored,
+    // Some(false) re-included by a ! rule, and None means that no rule is about the path at all.
+    pub fn matched(&self, relative_path: &Path, is_dir: bool) -> Option<bool> {
+        let names = Glob::names(relative_path);
+
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.glob.matches_names(&names))
+            .map(|rule| !rule.negated)
+    }
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Self> {
+        let line = trim_trailing_spaces(line);
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+
+        if line.trim_start_matches('/').is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            glob: Glob::new(line),
+            negated,
+            dir_only,
+        })
+    }
+}
+
+// Trailing spaces are dropped, unless they are escaped with a backslash
+fn trim_trailing_spaces(line: &str) -> &str {
+    let trimmed = line.trim_end_matches(' ');
+    if trimmed.ends_with('\\') && trimmed.len() < line.len() {
+        &line[..trimmed.len() + 1]
+    } else {
+        trimmed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignored(rules: &str, path: &str, is_dir: bool) -> Option<bool> {
+        Gitignore::parse(rules).matched(Path::new(path), is_dir)
+    }
+
+    #[test]
+    fn gitignore_matches_names_at_any_depth() {
+        assert_eq!(Some(true), ignored("*.log", "app.log", false));
+        assert_eq!(Some(true), ignored("*.log", "var/log/app.log", false));
+        assert_eq!(Some(true), ignored("target", "crates/core/target", true));
+        assert_eq!(None, ignored("*.log", "app.log.txt", false));
+    }
+
+    #[test]
+    fn gitignore_anchors_rules_with_a_slash() {
+        assert_eq!(Some(true), ignored("/build", "build", true));
+        assert_eq!(None, ignored("/build", "src/build", true));
+        assert_eq!(Some(true), ignored("doc/*.html", "doc/index.html", false));
+        assert_eq!(None, ignored("doc/*.html", "doc/api/index.html", false));
+    }
+
+    #[test]
+    fn gitignore_matches_any_directories_with_double_star() {
+        assert_eq!(Some(true), ignored("**/logs", "a/b/logs", true));
+        assert_eq!(Some(true), ignored("a/**/b", "a/b", true));
+        assert_eq!(Some(true), ignored("a/**/b", "a/x/y/b", true));
+        assert_eq!(Some(true), ignored("logs/**", "logs/debug/today.log", false));
+        assert_eq!(None, ignored("logs/**", "logs", true));
+    }
+
+    #[test]
+    fn gitignore_only_matches_directories_with_trailing_slash() {
+        assert_eq!(Some(true), ignored("node_modules/", "web/node_modules", true));
+        assert_eq!(None, ignored("node_modules/", "web/node_modules", false));
+    }
+
+    #[test]
+    fn gitignore_last_matching_rule_wins() {
+        let rules = "*.log\n!keep.log\n";
+
+        assert_eq!(Some(true), ignored(rules, "debug.log", false));
+        assert_eq!(Some(false), ignored(rules, "keep.log", false));
+        assert_eq!(Some(true), ignored("!keep.log\n*.log", "keep.log", false));
+    }
+
+    #[test]
+    fn gitignore_skips_comments_and_handles_escapes() {
+        assert!(Gitignore::parse("# comment\n\n   \n").is_empty());
+        assert_eq!(Some(true), ignored("\\#notes", "#notes", false));
+        assert_eq!(Some(true), ignored("\\!important", "!important", false));
+        assert_eq!(Some(true), ignored("trailing\\ ", "trailing ", false));
+    }
+
+    #[test]
+    fn gitignore_matches_classes_and_single_chars() {
+        assert_eq!(Some(true), ignored("file[0-9].txt", "file7.txt", false));
+        assert_eq!(None, ignored("file[!0-9].txt", "file7.txt", false));
+        assert_eq!(Some(true), ignored("?.o", "a.o", false));
+        assert_eq!(None, ignored("?.o", "ab.o", false));
+    }
+}
@@ -0,0 +1,189 @@
+use std::fmt::Write;
+
+use crate::model::{Line, LineKind};
+use crate::stats::Stats;
+
+// Builds the JSON Lines events that --json prints, one JSON object per line, so other programs can
+// read the results without parsing the normal output. A search of a file is reported as:
+//
+//   {"type":"begin","data":{"path":{"text":"app.log"}}}
+//   {"type":"match","data":{"path":...,"lines":{"text":"..."},"line_number":3,"absolute_offset":120,"submatches":[...]}}
+//   {"type":"context","data":{...}}
+//   {"type":"end","data":{"path":...,"stats":{...}}}
+//
+// and after all files a single {"type":"summary",...} event follows.
+//
+// Text that is not valid UTF-8 can not be put in a JSON string, so it is written as {"bytes":"..."}
+// with the bytes encoded in base64, and valid text is written as {"text":"..."}.
+
+pub fn begin_event(path: &[u8]) -> String {
+    format!(r#"{{"type":"begin","data":{{"path":{}}}}}"#, data(path))
+}
+
+// A selected line is a "match" event and a context line is a "context" event
+pub fn line_event(path: &[u8], line: &Line) -> String {
+    let event_type = match line.kind {
+        LineKind::Match => "match",
+        LineKind::Context => "context",
+    };
+
+    let mut submatches: Vec<String> = Vec::new();
+    for found_match in &line.matches {
+        let bytes = &line.text[found_match.byte_range.clone()];
+        submatches.push(format!(
+            r#"{{"match":{},"start":{},"end":{}}}"#,
+            data(bytes),
+            found_match.byte_range.start,
+            found_match.byte_range.end
+        ));
+    }
+
+    format!(
+        r#"{{"type":"{event_type}","data":{{"path":{},"lines":{},"line_number":{},"absolute_offset":{},"submatches":[{}]}}}}"#,
+        data(path),
+        data(&line.text[..]),
+        line.index,
+        line.byte_offset,
+        submatches.join(",")
+    )
+}
+
+pub fn end_event(path: &[u8], stats: &Stats) -> String {
+    format!(
+        r#"{{"type":"end","data":{{"path":{},"stats":{{"bytes_read":{},"lines_matched":{},"matches":{}}}}}}}"#,
+        data(path),
+        stats.bytes_read,
+        stats.lines_matched,
+        stats.matches
+    )
+}
+
+pub fn summary_event(stats: &Stats) -> String {
+    format!(
+        r#"{{"type":"summary","data":{{"elapsed_secs":{:.6},"stats":{{"files_scanned":{},"bytes_read":{},"lines_matched":{},"matches":{}}}}}}}"#,
+        stats.elapsed.as_secs_f64(),
+        stats.files_scanned,
+        stats.bytes_read,
+        stats.lines_matched,
+        stats.matches
+    )
+}
+
+// Writes bytes as {"text":"..."} when they are valid UTF-8, and as {"bytes":"..."} in base64 otherwise
+pub fn data(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => format!(r#"{{"text":{}}}"#, escape(text)),
+        Err(_) => format!(r#"{{"bytes":"{}"}}"#, base64(bytes)),
+    }
+}
+
+// Quotes a string for JSON. Quotes, backslashes and all control chars must be escaped,
+// every other char (including non-ASCII ones) can be written as it is.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\u{8}' => escaped.push_str("\\b"),
+            '\u{c}' => escaped.push_str("\\f"),
+            c if c < ' ' => {
+                // Writing to a String can not fail
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Standard base64 with padding. Every 3 bytes become 4 chars of 6 bits each.
+pub fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let b0 = chunk[0] as u32;
+        let b1 = chunk.get(1).copied().unwrap_or(0) as u32;
+        let b2 = chunk.get(2).copied().unwrap_or(0) as u32;
+        let triple = (b0 << 16) | (b1 << 8) | b2;
+
+        for i in 0..4 {
+            // A chunk of n bytes fills n + 1 chars, the rest is padding
+            if i <= chunk.len() {
+                let index = (triple >> (18 - 6 * i)) & 0x3f;
+                encoded.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Match;
+
+    #[test]
+    fn escape_quotes_and_escapes_special_chars() {
+        assert_eq!(r#""say \"hi\" \\ bye""#, escape(r#"say "hi" \ bye"#));
+        assert_eq!(r#""a\tb\r\n""#, escape("a\tb\r\n"));
+        assert_eq!(r#""\u0000\u001b[31m\b\f""#, escape("\0\x1b[31m\u{8}\u{c}"));
+    }
+
+    #[test]
+    fn escape_keeps_non_ascii_chars() {
+        assert_eq!(r#""Hej på dig ✓""#, escape("Hej på dig ✓"));
+    }
+
+    #[test]
+    fn base64_pads_the_last_chunk() {
+        assert_eq!("", base64(b""));
+        assert_eq!("Zg==", base64(b"f"));
+        assert_eq!("Zm8=", base64(b"fo"));
+        assert_eq!("Zm9v", base64(b"foo"));
+        assert_eq!("Zm9vYmFy", base64(b"foobar"));
+        assert_eq!("/+8A", base64(&[0xff, 0xef, 0x00]));
+    }
+
+    #[test]
+    fn data_uses_bytes_for_invalid_utf8() {
+        assert_eq!(r#"{"text":"app.log"}"#, data(b"app.log"));
+        assert_eq!(r#"{"bytes":"YfFi"}"#, data(&[b'a', 0xf1, b'b']));
+    }
+
+    #[test]
+    fn line_event_has_line_number_offset_and_submatches() {
+        let mut line = Line::new(3, String::from("to be or \"not\" to be"));
+        line.byte_offset = 120;
+        line.matches = vec![Match::new(3, 0..2, 0..2), Match::new(3, 15..17, 15..17)];
+
+        assert_eq!(
+            concat!(
+                r#"{"type":"match","data":{"path":{"text":"poem.txt"},"lines":{"text":"to be or \"not\" to be"},"#,
+                r#""line_number":3,"absolute_offset":120,"submatches":["#,
+                r#"{"match":{"text":"to"},"start":0,"end":2},{"match":{"text":"to"},"start":15,"end":17}]}}"#
+            ),
+            line_event(b"poem.txt", &line)
+        )
+    }
+
+    #[test]
+    fn line_event_marks_context_lines() {
+        let line = Line::context(4, String::from("b"));
+
+        assert!(line_event(b"-", &line).starts_with(r#"{"type":"context","#));
+        assert!(line_event(b"-", &line).ends_with(r#""submatches":[]}}"#));
+    }
+}
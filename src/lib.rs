@@ -1,8 +1,26 @@
 pub mod model;
 
+pub mod aho_corasick;
+pub mod matcher;
+pub mod regex;
+
 pub mod display;
 pub mod highlight;
+pub mod json;
 pub mod search;
 
 pub mod runner;
-pub mod config;
\ No newline at end of file
+pub mod reader;
+pub mod mmap;
+pub mod encoding;
+pub mod gzip;
+pub mod tar;
+pub mod args;
+pub mod error;
+pub mod config;
+pub mod stats;
+pub mod walk;
+pub mod ignore;
+pub mod glob;
+pub mod filter;
+pub mod types;
\ No newline at end of file
@@ -1,27 +1,38 @@
-use std::{env, process};
+use std::{env, io, process};
 
-use minigrep::config::{Config, print_help};
+use minigrep::config::{Config, is_help_requested, print_help, print_type_list};
 use minigrep::runner;
 
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    if args.contains(&String::from("--help")) {
+    if is_help_requested(&args) {
         print_help();
         return;
     }
 
+    // Like grep, 2 means something went wrong, while 1 only means that nothing matched
     let config = Config::build(&args).unwrap_or_else(|err| {
         eprintln!("Problem parsing arguments: {err}");
-        println!("Usage: minigrep [OPTIONS] <PATTERN> <FILE>");
-        println!("Try 'minigrep --help' for more information.");
-        process::exit(1);
+        eprintln!("Usage: minigrep [OPTIONS] <PATTERN> [FILE]...");
+        eprintln!("Try 'minigrep --help' for more information.");
+        process::exit(2);
     });
 
-    if let Err(e) = runner::run(config) {
-        eprintln!("Application error: {e}");
-        process::exit(1);
+    if config.type_list {
+        if let Err(e) = print_type_list(&mut io::stdout().lock(), &config.file_types) {
+            eprintln!("Application error: {e}");
+            process::exit(2);
+        }
+        return;
     }
-}
 
+    match runner::run(config) {
+        Ok(outcome) => process::exit(outcome.exit_code()),
+        Err(e) => {
+            eprintln!("Application error: {e}");
+            process::exit(2);
+        }
+    }
+}
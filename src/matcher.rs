@@ -0,0 +1,333 @@
+use crate::aho_corasick::AhoCorasick;
+use crate::error::MinigrepError;
+use crate::regex::{Regex, decode_char, decode_last_char, is_word_char};
+
+// The matcher is built once from the config and then used for every line the search goes through.
+// It works on bytes, so text that is not valid UTF-8 can still be searched: every byte that is not
+// part of a valid char is read as a U+FFFD replacement char, and all positions are byte offsets.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    pattern: Pattern,
+    only_match_words: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    // A single case sensitive literal is fastest to find as a plain run of bytes
+    Literal(Vec<u8>),
+    // Any other set of literals is matched in one pass with an Aho-Corasick automaton
+    Literals(AhoCorasick),
+    Regex(Regex),
+}
+
+impl Matcher {
+    // A line matches if any of the patterns match it
+    pub fn new<S: AsRef<str>>(
+        patterns: &[S],
+        ignore_case: bool,
+        only_match_words: bool,
+        use_regex: bool,
+    ) -> Result<Self, MinigrepError> {
+        let pattern = match patterns {
+            _ if use_regex && !patterns.is_empty() => {
+                Pattern::Regex(combine_regexes(patterns, ignore_case, only_match_words)?)
+            }
+            [query] if !ignore_case => Pattern::Literal(query.as_ref().as_bytes().to_vec()),
+            _ => Pattern::Literals(AhoCorasick::new(patterns, ignore_case)),
+        };
+
+        Ok(Self {
+            pattern,
+            only_match_words,
+        })
+    }
+
+    pub fn is_match(&self, text: &[u8]) -> bool {
+        match &self.pattern {
+            // The regex and the automaton have faster ways of checking for a match than finding it.
+            // A regex does its own whole word check.
+            Pattern::Regex(regex) => regex.is_match(text),
+            Pattern::Literals(automaton) if !self.only_match_words => automaton.is_match(text),
+            _ => self.find_at(text, 0).is_some(),
+        }
+    }
+
+    // Returns the byte range of the first match starting at or after the byte offset start
+    pub fn find_at(&self, text: &[u8], start: usize) -> Option<(usize, usize)> {
+        // Like a regex, the automaton only finds whole words to begin with, trying every pattern
+        // that starts at a place before it moves on
+        if let Pattern::Literals(automaton) = &self.pattern
+            && self.only_match_words
+        {
+            return automaton.find_at_where(text, start, |match_start, match_end| {
+                is_whole_word(text, match_start, match_end)
+            });
+        }
+
+        let mut start = start;
+
+        loop {
+            let (match_start, match_end) = self.find_candidate(text, start)?;
+
+            // With whole word matching, a single literal that is glued to other word chars does not
+            // count, so the search goes on from the next char
+            let checks_words = self.only_match_words && matches!(self.pattern, Pattern::Literal(_));
+            if !checks_words || is_whole_word(text, match_start, match_end) {
+                return Some((match_start, match_end));
+            }
+
+            start = match_start + decode_char(text, match_start)?.1;
+        }
+    }
+
+    // Returns the byte ranges of all non overlapping, non empty matches
+    pub fn find_iter(&self, text: &[u8]) -> Vec<(usize, usize)> {
+        let mut matches = Vec::new();
+        let mut start = 0;
+
+        while let Some((match_start, match_end)) = self.find_at(text, start) {
+            if match_start == match_end {
+                // Empty matches (like "a*" on "b") have nothing to show, so they are skipped
+                match decode_char(text, match_end) {
+                    Some((_, len)) => start = match_end + len,
+                    None => break,
+                }
+            } else {
+                matches.push((match_start, match_end));
+                start = match_end;
+            }
+        }
+
+        matches
+    }
+
+    // For searching a whole text of many lines at once: returns a position such that no line from
+    // start up to the line the position is in can be selected, while that line still has to be checked.
+    // start has to be the start of a line.
+    pub fn find_line_candidate(&self, text: &[u8], start: usize) -> Option<usize> {
+        match &self.pattern {
+            Pattern::Regex(regex) => regex.find_line_candidate(text, start),
+            // Whole word matching only ever drops matches, so the first raw match is early enough
+            _ => self.find_candidate(text, start).map(|(match_start, _)| match_start),
+        }
+    }
+
+    fn find_candidate(&self, text: &[u8], start: usize) -> Option<(usize, usize)> {
+        match &self.pattern {
+            Pattern::Literal(query) => find_bytes(&text[start..], query)
+                .map(|index| (start + index, start + index + query.len())),
+            Pattern::Literals(automaton) => automaton.find_at(text, start),
+            Pattern::Regex(regex) => regex.find_at(text, start),
+        }
+    }
+}
+
+// Several regexes are joined into one alternation, so all of them are matched in a single pass.
+// Each one is compiled on its own first, so an error points into the pattern that has it.
+fn combine_regexes<S: AsRef<str>>(
+    patterns: &[S],
+    ignore_case: bool,
+    only_match_words: bool,
+) -> Result<Regex, MinigrepError> {
+    if let [pattern] = patterns {
+        return compile_regex(pattern.as_ref(), ignore_case, only_match_words);
+    }
+
+    let mut groups = Vec::new();
+    for pattern in patterns {
+        compile_regex(pattern.as_ref(), ignore_case, false)?;
+        groups.push(format!("(?:{})", pattern.as_ref()));
+    }
+
+    compile_regex(&groups.join("|"), ignore_case, only_match_words)
+}
+
+fn compile_regex(pattern: &str, ignore_case: bool, only_match_words: bool) -> Result<Regex, MinigrepError> {
+    let regex = if only_match_words {
+        Regex::whole_words(pattern, ignore_case)
+    } else {
+        Regex::new(pattern, ignore_case)
+    };
+
+    regex.map_err(|error| MinigrepError::InvalidRegex {
+        pattern: String::from(pattern),
+        error,
+    })
+}
+
+// Looks for the first byte of the needle and then checks the rest, which is quick since the
+// first byte of a pattern is rarely common in the text
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    let Some((&first, rest)) = needle.split_first() else {
+        return Some(0);
+    };
+
+    let mut start = 0;
+    while let Some(index) = haystack[start..].iter().position(|&byte| byte == first) {
+        let at = start + index;
+        if haystack[at + 1..].starts_with(rest) {
+            return Some(at);
+        }
+        start = at + 1;
+    }
+
+    None
+}
+
+fn is_whole_word(text: &[u8], start: usize, end: usize) -> bool {
+    let before = decode_last_char(text, start);
+    let after = decode_char(text, end).map(|(c, _)| c);
+
+    !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literal_matcher_finds_all_occurrences() {
+        let matcher = Matcher::new(&["be"], false, false, false).unwrap();
+
+        assert_eq!(vec![(3, 5), (16, 18)], matcher.find_iter(b"To be or not to be"))
+    }
+
+    #[test]
+    fn literal_matcher_ignores_case_when_enabled() {
+        let matcher = Matcher::new(&["ÅSA"], true, false, false).unwrap();
+
+        assert!(matcher.is_match("hej åsa".as_bytes()));
+        assert_eq!(vec![(4, 8)], matcher.find_iter("hej åsa".as_bytes()))
+    }
+
+    #[test]
+    fn matcher_only_matches_whole_words_when_enabled() {
+        let matcher = Matcher::new(&["to"], false, true, false).unwrap();
+
+        assert!(!matcher.is_match(b"Me too"));
+        assert_eq!(vec![(13, 15)], matcher.find_iter(b"tomato to_do to"))
+    }
+
+    #[test]
+    fn literal_matcher_tries_shorter_patterns_for_whole_words() {
+        // The longest pattern at the start is glued to the rest of the word, the shorter one is not
+        let matcher = Matcher::new(&["foo", "foo-b"], false, true, false).unwrap();
+        assert!(matcher.is_match(b"foo-bar"));
+        assert_eq!(vec![(0, 3)], matcher.find_iter(b"foo-bar"));
+
+        let matcher = Matcher::new(&["ERR", "error"], true, true, false).unwrap();
+        assert_eq!(vec![(0, 3), (10, 15)], matcher.find_iter(b"err-ors x error"));
+        assert!(!matcher.is_match(b"errors"));
+    }
+
+    #[test]
+    fn regex_matcher_finds_all_occurrences() {
+        let matcher = Matcher::new(&[r"err(or)?\s+\d+"], false, false, true).unwrap();
+
+        assert_eq!(vec![(0, 5), (10, 19)], matcher.find_iter(b"err 1 and error  42"))
+    }
+
+    #[test]
+    fn regex_matcher_only_matches_whole_words_when_enabled() {
+        let matcher = Matcher::new(&["a.c"], false, true, true).unwrap();
+
+        assert_eq!(vec![(6, 9)], matcher.find_iter(b"xabcx abc"))
+    }
+
+    #[test]
+    fn regex_matcher_tries_other_lengths_for_whole_words() {
+        // The first alternative matches at the start too, but is glued to the rest of the word
+        let matcher = Matcher::new(&["foo|foobar"], false, true, true).unwrap();
+        assert_eq!(vec![(0, 6)], matcher.find_iter(b"foobar baz"));
+
+        // Several -e patterns are one alternation, so they have to work the same way
+        let matcher = Matcher::new(&["err", "error"], false, true, true).unwrap();
+        assert!(matcher.is_match(b"error: disk"));
+        assert_eq!(vec![(0, 5)], matcher.find_iter(b"error: disk"));
+
+        // Like grep, a pattern that starts with a non word char is a whole word next to a space
+        let matcher = Matcher::new(&["-x"], false, true, true).unwrap();
+        assert_eq!(vec![(2, 4)], matcher.find_iter(b"a -x b-xc"));
+    }
+
+    #[test]
+    fn regex_matcher_skips_empty_matches() {
+        let matcher = Matcher::new(&["x*"], false, false, true).unwrap();
+
+        assert!(matcher.is_match(b"abc"));
+        assert_eq!(vec![(1, 3)], matcher.find_iter(b"axxb"))
+    }
+
+    #[test]
+    fn matcher_finds_line_candidates_in_text_of_many_lines() {
+        let text = b"tomato\nto do\n";
+
+        let matcher = Matcher::new(&["to"], false, true, false).unwrap();
+        assert_eq!(Some(0), matcher.find_line_candidate(text, 0));
+        assert_eq!(Some(7), matcher.find_line_candidate(text, 7));
+
+        let matcher = Matcher::new(&["do$"], false, false, true).unwrap();
+        assert_eq!(Some(12), matcher.find_line_candidate(text, 0));
+    }
+
+    #[test]
+    fn matcher_finds_matches_in_text_that_is_not_utf8() {
+        let text = b"caf\xe9: error \xff in caf\xe9";
+
+        let matcher = Matcher::new(&["error"], false, true, false).unwrap();
+        assert_eq!(vec![(6, 11)], matcher.find_iter(text));
+
+        let matcher = Matcher::new(&["caf"], false, false, false).unwrap();
+        assert_eq!(vec![(0, 3), (17, 20)], matcher.find_iter(text));
+
+        let matcher = Matcher::new(&["in", "ERROR"], true, false, false).unwrap();
+        assert_eq!(vec![(6, 11), (14, 16)], matcher.find_iter(text));
+    }
+
+    #[test]
+    fn matcher_new_fails_on_invalid_regex() {
+        assert!(Matcher::new(&["a[b"], false, false, true).is_err());
+        assert!(Matcher::new(&["a[b"], false, false, false).is_ok());
+    }
+
+    #[test]
+    fn literal_matcher_finds_occurrences_of_every_pattern() {
+        let matcher = Matcher::new(&["E42", "E7", "E4"], false, false, false).unwrap();
+
+        assert_eq!(vec![(0, 3), (8, 10)], matcher.find_iter(b"E42 and E7 but not E9"))
+    }
+
+    #[test]
+    fn regex_matcher_finds_occurrences_of_every_pattern() {
+        let matcher = Matcher::new(&[r"E\d{2}", "warn(ing)?"], true, false, true).unwrap();
+
+        assert_eq!(vec![(0, 7), (8, 11)], matcher.find_iter(b"Warning E12 e3"))
+    }
+
+    #[test]
+    fn regex_matcher_reports_error_position_in_failing_pattern() {
+        let error = Matcher::new(&["ok", "a(b"], false, false, true).unwrap_err();
+
+        match error {
+            MinigrepError::InvalidRegex { pattern, error } => {
+                assert_eq!("a(b", pattern);
+                assert_eq!(1, error.position);
+            }
+            _ => panic!("expected an invalid regex error"),
+        }
+    }
+
+    #[test]
+    fn matcher_without_patterns_matches_nothing() {
+        let matcher = Matcher::new::<String>(&[], false, false, true).unwrap();
+
+        assert!(!matcher.is_match(b"anything"))
+    }
+
+    #[test]
+    fn literal_matcher_ignores_case_for_every_pattern() {
+        let matcher = Matcher::new(&["timeout", "REFUSED"], true, false, false).unwrap();
+
+        assert_eq!(vec![(0, 7), (12, 19)], matcher.find_iter(b"TimeOut and refused"))
+    }
+}
@@ -0,0 +1,115 @@
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+
+// A read only memory map of a whole file, so a big file can be searched as one buffer without
+// copying it through a reader first. The pages are only read from disk when they are touched.
+//
+// The map is private, but if another process shrinks the file while it is mapped, touching the
+// missing pages kills the process with SIGBUS. That is the price every grep with mmap pays, so it
+// is only used for big regular files where the speed is worth it.
+pub struct Mmap {
+    ptr: *const u8,
+    len: usize,
+}
+
+// The few libc calls needed, declared by hand so no crate is needed.
+// The constants have the same values on Linux and the BSDs (macOS included).
+#[cfg(all(unix, target_pointer_width = "64"))]
+mod sys {
+    use std::ffi::c_void;
+
+    pub const PROT_READ: i32 = 1;
+    pub const MAP_PRIVATE: i32 = 2;
+    pub const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+    unsafe extern "C" {
+        pub fn mmap(addr: *mut c_void, len: usize, prot: i32, flags: i32, fd: i32, offset: i64) -> *mut c_void;
+        pub fn munmap(addr: *mut c_void, len: usize) -> i32;
+    }
+}
+
+impl Mmap {
+    #[cfg(all(unix, target_pointer_width = "64"))]
+    pub fn map(file: &File) -> io::Result<Self> {
+        use std::os::fd::AsRawFd;
+
+        let len = usize::try_from(file.metadata()?.len()).map_err(io::Error::other)?;
+        // Mapping nothing is an error for mmap, and there is nothing to search anyway
+        if len == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Can not map an empty file"));
+        }
+
+        // Safety: the arguments ask for a new read only mapping of an open file, which does not touch
+        // any memory Rust knows about. The result is checked before it is used.
+        let ptr = unsafe {
+            sys::mmap(
+                std::ptr::null_mut(),
+                len,
+                sys::PROT_READ,
+                sys::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == sys::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self { ptr: ptr as *const u8, len })
+    }
+
+    // Elsewhere the caller falls back on reading the file
+    #[cfg(not(all(unix, target_pointer_width = "64")))]
+    pub fn map(_file: &File) -> io::Result<Self> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // Safety: the mapping is len bytes long, readable and lives until the Mmap is dropped
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        #[cfg(all(unix, target_pointer_width = "64"))]
+        // Safety: the mapping came from mmap with this length and nothing borrows it any more
+        unsafe {
+            sys::munmap(self.ptr as *mut std::ffi::c_void, self.len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    #[cfg(all(unix, target_pointer_width = "64"))]
+    fn mmap_reads_the_whole_file() {
+        let path = std::env::temp_dir().join(format!("minigrep-mmap-{}.txt", std::process::id()));
+        File::create(&path).unwrap().write_all(b"first\nsecond\n").unwrap();
+
+        let map = Mmap::map(&File::open(&path).unwrap()).unwrap();
+        assert_eq!(b"first\nsecond\n", &map[..]);
+
+        drop(map);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn mmap_fails_on_empty_file() {
+        let path = std::env::temp_dir().join(format!("minigrep-mmap-empty-{}.txt", std::process::id()));
+        File::create(&path).unwrap();
+
+        assert!(Mmap::map(&File::open(&path).unwrap()).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,3 +1,6 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
 #[derive(Debug, PartialEq)]
 pub struct Content {
     pub lines: Vec<Line>,
@@ -12,18 +15,21 @@ impl Content {
         Self { lines }
     }
 
+    #[allow(clippy::should_implement_trait)] // Parsing a text into lines can never fail, so FromStr does not fit
     pub fn from_str(text: &str) -> Self {
-        let mut lines: Vec<Line> = Vec::new();
-
-        for (index, text_line) in text.lines().enumerate() {
-            let line = Line::new(
-                index + 1, // Line index start at 1, not 0
-                String::from(text_line),
-            );
-            lines.push(line)
-        }
+        Self::from_bytes(text.as_bytes())
+    }
 
-        Self { lines }
+    // The text does not have to be valid UTF-8, since lines are searched as bytes
+    pub fn from_bytes(text: &[u8]) -> Self {
+        // Reading from a slice can not fail, since it is already in memory
+        Self::from_reader(text).unwrap_or_default()
+    }
+}
+
+impl Default for Content {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -31,12 +37,69 @@ impl Content {
 #[derive(Debug, PartialEq, Clone)]
 pub struct Line {
     pub index: usize,
-    pub text: String,
+    // The bytes of the line as they are in the input, without the line break. They are usually
+    // UTF-8, but do not have to be, and are only turned into a str when the line is printed.
+    pub text: Vec<u8>,
+    // Where the line starts, counted in bytes from the start of the text that is searched. For an input
+    // that is decompressed or transcoded to UTF-8, that is the text after it, not the file.
+    pub byte_offset: u64,
+    pub kind: LineKind,
+    // Where the query matched in the text, filled in by the search for the selected lines
+    pub matches: Vec<Match>,
+}
+
+// A line is either one that the search selected, or one that is only shown
+// as context around a selected line (-A, -B and -C)
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LineKind {
+    Match,
+    Context,
+}
+
+// A single match inside a line. The byte range is for slicing the text, and the char range is
+// for showing positions to people (like --column), since a char can take several bytes.
+// A byte that is not valid UTF-8 counts as one char, like the U+FFFD it is printed as.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Match {
+    pub line_index: usize,
+    pub byte_range: Range<usize>,
+    pub char_range: Range<usize>,
+}
+
+impl Match {
+    pub fn new(line_index: usize, byte_range: Range<usize>, char_range: Range<usize>) -> Self {
+        Self {
+            line_index,
+            byte_range,
+            char_range,
+        }
+    }
 }
 
 impl Line {
-    pub fn new(index: usize, text: String) -> Self {
-        Self { index, text }
+    pub fn new(index: usize, text: impl Into<Vec<u8>>) -> Self {
+        Self {
+            index,
+            text: text.into(),
+            byte_offset: 0,
+            kind: LineKind::Match,
+            matches: Vec::new(),
+        }
+    }
+
+    pub fn context(index: usize, text: impl Into<Vec<u8>>) -> Self {
+        Self {
+            index,
+            text: text.into(),
+            byte_offset: 0,
+            kind: LineKind::Context,
+            matches: Vec::new(),
+        }
+    }
+
+    // The text for printing, where the bytes that are not valid UTF-8 are shown as U+FFFD
+    pub fn text_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.text)
     }
 }
 
@@ -59,9 +122,9 @@ mod tests {
         let content = Content::from_lines(lines);
 
         assert_eq!(content.lines[0].index, first_index);
-        assert_eq!(content.lines[0].text, first_text);
+        assert_eq!(content.lines[0].text, first_text.as_bytes());
         assert_eq!(content.lines[1].index, second_index);
-        assert_eq!(content.lines[1].text, second_text);
+        assert_eq!(content.lines[1].text, second_text.as_bytes());
     }
 
     #[test]
@@ -71,10 +134,10 @@ mod tests {
         let content = Content::from_str(text);
 
         assert_eq!(content.lines[0].index, 1);
-        assert_eq!(content.lines[0].text, "Hello World!");
+        assert_eq!(content.lines[0].text, b"Hello World!");
         assert_eq!(content.lines[1].index, 2);
-        assert_eq!(content.lines[1].text, "By World!");
-        assert_eq!(content.lines[2].text, "I like Rust :)");
+        assert_eq!(content.lines[1].text, b"By World!");
+        assert_eq!(content.lines[2].text, b"I like Rust :)");
     }
 
     #[test]
@@ -85,6 +148,25 @@ mod tests {
         let line = Line::new(index, text.clone());
 
         assert_eq!(line.index, 39);
-        assert_eq!(line.text, text);
+        assert_eq!(line.text, text.as_bytes());
+        assert_eq!(line.kind, LineKind::Match);
+        assert!(line.matches.is_empty());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn line_context_is_marked_as_context() {
+        let line = Line::context(4, String::from("Hello World!"));
+
+        assert_eq!(line.index, 4);
+        assert_eq!(line.kind, LineKind::Context);
+    }
+
+    #[test]
+    fn content_from_bytes_keeps_bytes_that_are_not_utf8() {
+        let content = Content::from_bytes(b"caf\xe9\nok");
+
+        assert_eq!(content.lines[0].text, b"caf\xe9");
+        assert_eq!(content.lines[0].text_lossy(), "caf\u{FFFD}");
+        assert_eq!(content.lines[1].text_lossy(), "ok");
+    }
+}
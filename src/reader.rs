@@ -0,0 +1,323 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+
+use crate::matcher::Matcher;
+use crate::model::{Content, Line};
+
+// Anything the runner can search: it yields the lines and knows how much of the input it went through
+pub trait LineSource: Iterator<Item = Line> {
+    // The number of bytes gone through so far, line breaks included
+    fn bytes_read(&self) -> u64;
+}
+
+// Reads lines lazily from any buffered reader, so a search only ever holds the lines it still needs,
+// no matter how big the input is. Every line gets its line number and the byte offset it starts at.
+//
+// The iterator yields plain lines, so it can be handed straight to a Search. A read error ends the
+// iteration and is kept, so the caller can check for it with take_error once the search is done.
+pub struct LineReader<R> {
+    reader: R,
+    // Every line is read into this buffer, which is kept so it only has to grow for a line longer than
+    // any before it. The line is then copied out into a Vec of its own that fits it exactly, since
+    // lines are handed on by value and some of them are kept around as context.
+    buffer: Vec<u8>,
+    index: usize,
+    bytes_read: u64,
+    error: Option<io::Error>,
+    // Set at the end of the input or at a read error, after which the reader is in an unknown state
+    finished: bool,
+}
+
+impl<R: BufRead> LineReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+            index: 0,
+            bytes_read: 0,
+            error: None,
+            finished: false,
+        }
+    }
+
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<Line>> {
+        self.buffer.clear();
+        let line_length = self.reader.read_until(b'\n', &mut self.buffer)?;
+        if line_length == 0 {
+            return Ok(None);
+        }
+
+        // The bytes are kept as they are, since lines are searched as bytes and need not be UTF-8.
+        // This copy is the one allocation per line.
+        let bytes = self.buffer.strip_suffix(b"\n").unwrap_or(&self.buffer);
+        let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+
+        self.index += 1; // Line index start at 1, not 0
+        let mut line = Line::new(self.index, bytes);
+        line.byte_offset = self.bytes_read;
+
+        self.bytes_read += line_length as u64;
+        Ok(Some(line))
+    }
+}
+
+impl<R: BufRead> Iterator for LineReader<R> {
+    type Item = Line;
+
+    fn next(&mut self) -> Option<Line> {
+        if self.finished {
+            return None;
+        }
+
+        let line = self.read_line().unwrap_or_else(|error| {
+            self.error = Some(error);
+            None
+        });
+        self.finished = line.is_none();
+
+        line
+    }
+}
+
+impl<R: BufRead> LineSource for LineReader<R> {
+    fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+}
+
+// Goes through a text that is already in memory (like a memory mapped file) by letting the matcher
+// scan the whole text for the next possible match, instead of checking it one line at a time.
+// Line boundaries and line numbers are only worked out around the hits.
+//
+// Only the lines that may be selected are yielded, plus the lines around them that may be needed as
+// context, all with their real line numbers. Handed to a Search, that gives the same result as every
+// line would, as long as the match is not inverted: the lines that are left out can neither be
+// selected nor be context. The text must not have "\r\n" line breaks though, since the matcher sees
+// the whole text, where a $ does not match in front of the "\r".
+pub struct CandidateLines<'t, 'm> {
+    text: &'t [u8],
+    matcher: &'m Matcher,
+    before_context: usize,
+    after_context: usize,
+    // Where the next line starts that is neither yielded nor skipped yet
+    position: usize,
+    // The number of lines before position
+    index: usize,
+    // Lines after a possible match that are yielded without looking for the next match
+    lines_to_yield: usize,
+    // The before context of a possible match, followed by the line it is in
+    pending: VecDeque<Line>,
+}
+
+impl<'t, 'm> CandidateLines<'t, 'm> {
+    pub fn new(text: &'t [u8], matcher: &'m Matcher, before_context: usize, after_context: usize) -> Self {
+        Self {
+            text,
+            matcher,
+            before_context,
+            after_context,
+            position: 0,
+            index: 0,
+            lines_to_yield: 0,
+            pending: VecDeque::new(),
+        }
+    }
+
+    fn next_line(&mut self) -> Line {
+        let rest = &self.text[self.position..];
+        let (text, line_length) = match rest.iter().position(|&byte| byte == b'\n') {
+            Some(end) => (&rest[..end], end + 1),
+            None => (rest, rest.len()),
+        };
+        let text = text.strip_suffix(b"\r").unwrap_or(text);
+
+        self.index += 1; // Line index start at 1, not 0
+        let mut line = Line::new(self.index, text);
+        line.byte_offset = self.position as u64;
+
+        self.position += line_length;
+        line
+    }
+
+    // Skips to the line of the next possible match and queues it up with its before context
+    fn queue_candidate(&mut self) {
+        let candidate = self.matcher.find_line_candidate(self.text, self.position);
+        // A match can end right after the last line break, but there is no line there
+        let Some(candidate) = candidate.filter(|&candidate| candidate < self.text.len() || !self.text.ends_with(b"\n")) else {
+            self.position = self.text.len();
+            return;
+        };
+
+        // The line break at the end of a line belongs to that line
+        let line_start = start_of_last_line(&self.text[..candidate]).max(self.position);
+
+        // Walks back over the before context, which is never in front of the lines already gone through
+        let mut context_start = line_start;
+        for _ in 0..self.before_context {
+            if context_start <= self.position {
+                break;
+            }
+            context_start = start_of_last_line(&self.text[..context_start - 1]).max(self.position);
+        }
+
+        self.index += count_line_breaks(&self.text[self.position..context_start]);
+        self.position = context_start;
+
+        while self.position <= line_start && self.position < self.text.len() {
+            let line = self.next_line();
+            self.pending.push_back(line);
+        }
+        self.lines_to_yield = self.after_context;
+    }
+}
+
+impl Iterator for CandidateLines<'_, '_> {
+    type Item = Line;
+
+    fn next(&mut self) -> Option<Line> {
+        if self.pending.is_empty() && self.position < self.text.len() {
+            if self.lines_to_yield > 0 {
+                self.lines_to_yield -= 1;
+                let line = self.next_line();
+                // A match in the after context needs an after context of its own
+                if self.matcher.is_match(&line.text) {
+                    self.lines_to_yield = self.after_context;
+                }
+                return Some(line);
+            }
+
+            self.queue_candidate();
+        }
+
+        self.pending.pop_front()
+    }
+}
+
+impl LineSource for CandidateLines<'_, '_> {
+    fn bytes_read(&self) -> u64 {
+        self.position as u64
+    }
+}
+
+fn count_line_breaks(text: &[u8]) -> usize {
+    text.iter().filter(|&&byte| byte == b'\n').count()
+}
+
+// Where the last line of the text starts, right after its last line break
+fn start_of_last_line(text: &[u8]) -> usize {
+    text.iter().rposition(|&byte| byte == b'\n').map_or(0, |index| index + 1)
+}
+
+impl Content {
+    // Reads the whole input into memory, which is handy for small inputs when using minigrep as a library.
+    // The command line tool never does this, it searches the lines as they are read.
+    pub fn from_reader<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut line_reader = LineReader::new(reader);
+        let lines: Vec<Line> = line_reader.by_ref().collect();
+
+        match line_reader.take_error() {
+            Some(error) => Err(error),
+            None => Ok(Self::from_lines(lines)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn line_reader_tracks_line_numbers_and_byte_offsets() {
+        let mut line_reader = LineReader::new("first\r\nsecond\n\nlast".as_bytes());
+
+        let lines: Vec<(usize, u64, String)> = line_reader
+            .by_ref()
+            .map(|line| (line.index, line.byte_offset, line.text_lossy().into_owned()))
+            .collect();
+
+        assert_eq!(
+            vec![
+                (1, 0, String::from("first")),
+                (2, 7, String::from("second")),
+                (3, 14, String::from("")),
+                (4, 15, String::from("last")),
+            ],
+            lines
+        );
+        assert_eq!(19, line_reader.bytes_read());
+    }
+
+    #[test]
+    fn line_reader_keeps_bytes_that_are_not_utf8() {
+        let mut line_reader = LineReader::new(&b"ok\n\xff\xfe\r\nlast\n"[..]);
+
+        assert_eq!(Some(b"ok".to_vec()), line_reader.next().map(|line| line.text));
+        assert_eq!(Some(b"\xff\xfe".to_vec()), line_reader.next().map(|line| line.text));
+        assert_eq!(Some(b"last".to_vec()), line_reader.next().map(|line| line.text));
+        assert!(line_reader.take_error().is_none());
+    }
+
+    #[test]
+    fn line_reader_reads_lazily() {
+        // An endless input works, since lines are only read when they are asked for
+        let endless = io::BufReader::new(io::repeat(b'a'));
+        let mut line_reader = LineReader::new(b"first\n".chain(endless));
+
+        assert_eq!(Some(b"first".to_vec()), line_reader.next().map(|line| line.text));
+    }
+
+    #[test]
+    fn content_from_reader_reads_every_line() {
+        let content = Content::from_reader("a\nb\n".as_bytes()).unwrap();
+
+        assert_eq!(
+            vec![b"a".to_vec(), b"b".to_vec()],
+            content.lines.into_iter().map(|line| line.text).collect::<Vec<Vec<u8>>>()
+        )
+    }
+
+    fn candidate_lines(text: &str, query: &str, context: usize) -> Vec<(usize, u64, String)> {
+        let matcher = Matcher::new(&[query], false, false, true).unwrap();
+
+        CandidateLines::new(text.as_bytes(), &matcher, context, context)
+            .map(|line| (line.index, line.byte_offset, line.text_lossy().into_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn candidate_lines_skips_lines_without_matches() {
+        let text = "a\nb\nmatch\r\nc\nmatch\n";
+
+        assert_eq!(
+            vec![(3, 4, String::from("match")), (5, 13, String::from("match"))],
+            candidate_lines(text, "match", 0)
+        );
+        assert_eq!(Vec::<(usize, u64, String)>::new(), candidate_lines(text, "^$", 0));
+    }
+
+    #[test]
+    fn candidate_lines_yields_context_around_matches() {
+        let text = "1\n2\n3 x\n4 x\n5\n6\n7\n8\n9 x";
+        let indexes = |context| candidate_lines(text, "x", context).into_iter().map(|(index, _, _)| index).collect::<Vec<usize>>();
+
+        assert_eq!(vec![3, 4, 9], indexes(0));
+        assert_eq!(vec![2, 3, 4, 5, 8, 9], indexes(1));
+        assert_eq!(vec![1, 2, 3, 4, 5, 6, 7, 8, 9], indexes(2));
+    }
+
+    #[test]
+    fn candidate_lines_counts_bytes_gone_through() {
+        let matcher = Matcher::new(&["b"], false, false, false).unwrap();
+        let mut lines = CandidateLines::new(b"a\nb\nc\n", &matcher, 0, 0);
+
+        assert_eq!(Some(b"b".to_vec()), lines.next().map(|line| line.text));
+        assert_eq!(4, lines.bytes_read());
+        assert_eq!(None, lines.next());
+        assert_eq!(6, lines.bytes_read());
+    }
+}
@@ -0,0 +1,326 @@
+// A small regular expression engine without any external crates.
+// The pattern is parsed into an AST, compiled into a Thompson NFA program, and then run by either
+// a lazy DFA (to quickly check if a line matches) or a Pike VM (to find where the matches are).
+mod class;
+mod compiler;
+mod dfa;
+mod parser;
+mod pikevm;
+
+use std::fmt;
+use std::sync::Mutex;
+
+use compiler::Program;
+use dfa::LazyDfa;
+use parser::{Assertion, Ast};
+
+pub use class::is_word_char;
+pub use parser::RegexError;
+
+pub struct Regex {
+    pattern: String,
+    program: Program,
+    // The DFA caches the states it has built, so it is behind a mutex to allow searching through &self
+    dfa: Option<Mutex<LazyDfa>>,
+}
+
+impl Regex {
+    pub fn new(pattern: &str, ignore_case: bool) -> Result<Self, RegexError> {
+        let ast = parser::parse(pattern, ignore_case)?;
+        Self::from_ast(pattern, &ast)
+    }
+
+    // Only matches that have no word char right before and right after them, for -w. The check is
+    // part of the regex, so when the first way the pattern matches is glued to a word (like foo in
+    // foobar for foo|foobar), the other ways it can match at the same place are still tried.
+    pub fn whole_words(pattern: &str, ignore_case: bool) -> Result<Self, RegexError> {
+        let ast = Ast::Concat(vec![
+            Ast::Assertion(Assertion::NoWordBefore),
+            Ast::Group(Box::new(parser::parse(pattern, ignore_case)?)),
+            Ast::Assertion(Assertion::NoWordAfter),
+        ]);
+        Self::from_ast(pattern, &ast)
+    }
+
+    fn from_ast(pattern: &str, ast: &Ast) -> Result<Self, RegexError> {
+        let program = compiler::compile(ast)?;
+
+        let dfa = if program.has_word_boundary {
+            None
+        } else {
+            Some(Mutex::new(LazyDfa::new()))
+        };
+
+        Ok(Self {
+            pattern: String::from(pattern),
+            program,
+            dfa,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn is_match(&self, text: &[u8]) -> bool {
+        match &self.dfa {
+            Some(dfa) => dfa
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .is_match(&self.program, text),
+            None => self.find_at(text, 0).is_some(),
+        }
+    }
+
+    // Finds where a match could be in a text of many lines, without working out the match itself.
+    // No line from start up to the line the returned position is in has a match, but that line might.
+    // start has to be the start of a line.
+    pub fn find_line_candidate(&self, text: &[u8], start: usize) -> Option<usize> {
+        match &self.dfa {
+            // The match that ends first ends in the first line that has a match, or before it
+            Some(dfa) => dfa
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .shortest_match_end(&self.program, text, start),
+            // The leftmost match starts in the first line that has a match, or before it
+            None => self.find_at(text, start).map(|(match_start, _)| match_start),
+        }
+    }
+
+    // Returns the byte range of the leftmost match starting at or after the byte offset start
+    pub fn find_at(&self, text: &[u8], start: usize) -> Option<(usize, usize)> {
+        pikevm::find_at(&self.program, text, start)
+    }
+
+    // Returns the byte ranges of all non overlapping matches, including empty ones
+    pub fn find_iter(&self, text: &[u8]) -> Vec<(usize, usize)> {
+        let mut matches: Vec<(usize, usize)> = Vec::new();
+        let mut start = 0;
+
+        while start <= text.len() {
+            let Some((match_start, match_end)) = self.find_at(text, start) else {
+                break;
+            };
+
+            // An empty match right where the previous match ended is skipped, so "a*" on "ab"
+            // finds "a" and the empty match before "b" is not reported twice
+            let touches_previous = matches.last().is_some_and(|&(_, end)| end == match_end);
+            if match_start != match_end || !touches_previous {
+                matches.push((match_start, match_end));
+            }
+
+            start = if match_start == match_end {
+                match decode_char(text, match_end) {
+                    Some((_, len)) => match_end + len,
+                    None => break,
+                }
+            } else {
+                match_end
+            };
+        }
+
+        matches
+    }
+}
+
+impl Clone for Regex {
+    // The clone gets its own empty DFA cache, so clones never wait on each other
+    fn clone(&self) -> Self {
+        Self {
+            pattern: self.pattern.clone(),
+            program: self.program.clone(),
+            dfa: self.dfa.as_ref().map(|_| Mutex::new(LazyDfa::new())),
+        }
+    }
+}
+
+impl fmt::Debug for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Regex").field(&self.pattern).finish()
+    }
+}
+
+// Decodes the char that starts at the byte offset at, and returns it with its length in bytes.
+// Text is searched as bytes, so it does not have to be valid UTF-8: a byte that does not start a
+// valid char is read as a U+FFFD replacement char of its own, which is also how it is printed.
+// Only the end of the text gives None.
+pub fn decode_char(haystack: &[u8], at: usize) -> Option<(char, usize)> {
+    let first = *haystack.get(at)?;
+    let len = match first {
+        0x00..=0x7F => return Some((first as char, 1)),
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => return Some((char::REPLACEMENT_CHARACTER, 1)),
+    };
+
+    let c = haystack
+        .get(at..at + len)
+        .and_then(|bytes| std::str::from_utf8(bytes).ok())
+        .and_then(|text| text.chars().next());
+    match c {
+        Some(c) => Some((c, len)),
+        None => Some((char::REPLACEMENT_CHARACTER, 1)),
+    }
+}
+
+// Decodes the char that ends right before the byte offset at
+pub fn decode_last_char(haystack: &[u8], at: usize) -> Option<char> {
+    let mut start = at.checked_sub(1)?;
+    // Continuation bytes look like 0b10xxxxxx
+    while start > 0 && at - start < 4 && haystack[start] & 0xC0 == 0x80 {
+        start -= 1;
+    }
+
+    // When the char found does not end right at at, the byte before at is one that is not valid on its own
+    match decode_char(haystack, start) {
+        Some((c, len)) if start + len == at => Some(c),
+        _ => Some(char::REPLACEMENT_CHARACTER),
+    }
+}
+
+// Counts the chars in the text, reading the bytes that are not valid UTF-8 like decode_char does
+pub fn char_count(text: &[u8]) -> usize {
+    let mut count = 0;
+    let mut at = 0;
+
+    while let Some((_, len)) = decode_char(text, at) {
+        count += 1;
+        at += len;
+    }
+
+    count
+}
+
+fn assertion_holds(assertion: Assertion, haystack: &[u8], at: usize) -> bool {
+    let previous = decode_last_char(haystack, at);
+    let next = decode_char(haystack, at).map(|(c, _)| c);
+
+    let is_word = |c: Option<char>| c.is_some_and(is_word_char);
+
+    match assertion {
+        Assertion::StartLine => previous.is_none() || previous == Some('\n'),
+        Assertion::EndLine => next.is_none() || next == Some('\n'),
+        Assertion::WordBoundary => is_word(previous) != is_word(next),
+        Assertion::NotWordBoundary => is_word(previous) == is_word(next),
+        Assertion::NoWordBefore => !is_word(previous),
+        Assertion::NoWordAfter => !is_word(next),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regex_is_match_with_dfa_and_pike_vm_agree() {
+        let cases = [
+            (r"err(or)?\s+\d+", "fatal error 42", true),
+            (r"err(or)?\s+\d+", "fatal error: 42", false),
+            (r"^(foo|bar)+$", "foobarfoo", true),
+            (r"^(foo|bar)+$", "foobarfo", false),
+            (r"colou?r", "Colour", false),
+            (r"[[:upper:]][a-z]{2,}", "say Hello", true),
+            (r"x{0}y", "y", true),
+        ];
+
+        for (pattern, text, expected) in cases {
+            let regex = Regex::new(pattern, false).unwrap();
+            assert_eq!(expected, regex.is_match(text.as_bytes()), "{pattern} on {text}");
+            assert_eq!(expected, regex.find_at(text.as_bytes(), 0).is_some(), "{pattern} on {text}");
+        }
+    }
+
+    #[test]
+    fn regex_ignores_case_when_enabled() {
+        let regex = Regex::new("colou?r", true).unwrap();
+
+        assert!(regex.is_match(b"COLOR"));
+        assert_eq!(vec![(4, 10)], regex.find_iter(b"The Colour"))
+    }
+
+    #[test]
+    fn regex_digit_class_rejects_other_numbers() {
+        for pattern in [r"^\d$", "^[[:digit:]]$"] {
+            let regex = Regex::new(pattern, false).unwrap();
+            assert!(regex.is_match(b"7"), "{pattern}");
+            for text in ["½", "²", "Ⅷ", "٣"] {
+                assert!(!regex.is_match(text.as_bytes()), "{pattern} on {text}");
+                assert!(regex.find_at(text.as_bytes(), 0).is_none(), "{pattern} on {text}");
+            }
+        }
+
+        let regex = Regex::new(r"^\p{Nd}+$", false).unwrap();
+        assert!(regex.is_match("٣7".as_bytes()));
+        assert!(!regex.is_match("½".as_bytes()));
+    }
+
+    #[test]
+    fn regex_find_iter_returns_all_matches() {
+        let regex = Regex::new(r"\d+", false).unwrap();
+
+        assert_eq!(vec![(0, 2), (3, 4), (8, 11)], regex.find_iter(b"12 3 ab 456"))
+    }
+
+    #[test]
+    fn regex_find_iter_advances_past_empty_matches() {
+        let regex = Regex::new("a*", false).unwrap();
+
+        assert_eq!(vec![(0, 1), (2, 2), (4, 6)], regex.find_iter("abéaa".as_bytes()))
+    }
+
+    #[test]
+    fn regex_without_dfa_still_finds_matches() {
+        let regex = Regex::new(r"\bbe\b", false).unwrap();
+
+        assert!(regex.dfa.is_none());
+        assert!(regex.is_match(b"to be or not"));
+        assert!(!regex.is_match(b"because"));
+    }
+
+    #[test]
+    fn regex_find_line_candidate_lands_in_first_matching_line() {
+        let text = b"no\nfatal error 42\nerror 7\n";
+
+        let regex = Regex::new(r"err(or)?\s+\d+$", false).unwrap();
+        assert_eq!(Some(17), regex.find_line_candidate(text, 0));
+        assert_eq!(Some(25), regex.find_line_candidate(text, 18));
+
+        let regex = Regex::new(r"\berror\b", false).unwrap();
+        assert_eq!(Some(9), regex.find_line_candidate(text, 0));
+        assert_eq!(None, regex.find_line_candidate(text, 26));
+    }
+
+    #[test]
+    fn regex_new_fails_on_invalid_pattern() {
+        let error = Regex::new("a(b", false).unwrap_err();
+
+        assert_eq!("Unclosed group at position 1", error.to_string())
+    }
+
+    #[test]
+    fn regex_matches_around_invalid_utf8() {
+        let text = b"caf\xe9 ok \xff\xfeerror";
+
+        assert_eq!(Some((8, 15)), Regex::new("..error", false).unwrap().find_at(text, 0));
+        assert_eq!(Some((5, 15)), Regex::new(r"\bok\b.*", false).unwrap().find_at(text, 0));
+        assert!(Regex::new("caf.$", false).unwrap().find_at(text, 0).is_none());
+        assert!(Regex::new("^caf. ok", false).unwrap().is_match(text));
+    }
+
+    #[test]
+    fn char_count_reads_invalid_bytes_as_single_chars() {
+        assert_eq!(3, char_count("aé€".as_bytes()));
+        assert_eq!(4, char_count(b"a\xe9\xffb"));
+        assert_eq!(Some('\u{FFFD}'), decode_last_char(b"a\xe2\x82", 3));
+    }
+
+    #[test]
+    fn decode_last_char_handles_multibyte_chars() {
+        let text = "aé€";
+
+        assert_eq!(Some('€'), decode_last_char(text.as_bytes(), text.len()));
+        assert_eq!(Some('é'), decode_last_char(text.as_bytes(), 3));
+        assert_eq!(None, decode_last_char(text.as_bytes(), 0));
+    }
+}
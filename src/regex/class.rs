@@ -0,0 +1,237 @@
+// A character class is a set of chars that a single position in the pattern can match.
+// It is used for [...] classes, escapes like \d and \w, and for case insensitive literals.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Class {
+    pub items: Vec<ClassItem>,
+    pub negated: bool,
+    pub ignore_case: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClassItem {
+    Range(char, char),
+    Named { class: NamedClass, negated: bool },
+}
+
+// Named classes are backed by the Unicode aware predicates on char
+// instead of big lookup tables, which keeps the engine small and dependency free.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NamedClass {
+    // \d and [[:digit:]] are only 0 to 9, like in grep and PCRE, while \p{Nd} is every decimal digit
+    Digit,
+    DecimalNumber,
+    Word,
+    Space,
+    Alphabetic,
+    Alphanumeric,
+    Lowercase,
+    Uppercase,
+    Numeric,
+    Control,
+    Punctuation,
+    HexDigit,
+    Ascii,
+}
+
+impl NamedClass {
+    // Looks up the name used in \p{...} and [[:...:]]
+    pub fn from_name(name: &str) -> Option<Self> {
+        let class = match name {
+            "d" | "digit" => Self::Digit,
+            "Nd" | "Decimal_Number" => Self::DecimalNumber,
+            "w" | "word" => Self::Word,
+            "s" | "space" | "White_Space" | "Whitespace" => Self::Space,
+            "L" | "Letter" | "alpha" | "Alphabetic" => Self::Alphabetic,
+            "alnum" | "Alphanumeric" => Self::Alphanumeric,
+            "Ll" | "lower" | "Lowercase" | "Lowercase_Letter" => Self::Lowercase,
+            "Lu" | "upper" | "Uppercase" | "Uppercase_Letter" => Self::Uppercase,
+            "N" | "Number" | "Numeric" => Self::Numeric,
+            "Cc" | "cntrl" | "Control" => Self::Control,
+            "P" | "punct" | "Punctuation" => Self::Punctuation,
+            "xdigit" | "Hex_Digit" => Self::HexDigit,
+            "ascii" | "ASCII" => Self::Ascii,
+            _ => return None,
+        };
+
+        Some(class)
+    }
+
+    pub fn matches(&self, c: char) -> bool {
+        match self {
+            Self::Digit => c.is_ascii_digit(),
+            Self::DecimalNumber => is_decimal_number(c),
+            Self::Word => is_word_char(c),
+            Self::Space => c.is_whitespace(),
+            Self::Alphabetic => c.is_alphabetic(),
+            Self::Alphanumeric => c.is_alphanumeric(),
+            Self::Lowercase => c.is_lowercase(),
+            Self::Uppercase => c.is_uppercase(),
+            Self::Numeric => c.is_numeric(),
+            Self::Control => c.is_control(),
+            Self::Punctuation => c.is_ascii_punctuation() || is_unicode_punctuation(c),
+            Self::HexDigit => c.is_ascii_hexdigit(),
+            Self::Ascii => c.is_ascii(),
+        }
+    }
+}
+
+// Word chars are the ones \w, \b and whole word matching (-w) agree on
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// The decimal digits (category Nd) always come in runs of ten, from 0 to 9, so they are known by
+// where each run starts, as of Unicode 16. is_numeric is not enough, since it is also true for
+// chars like ½, ² and Ⅷ, which are numbers but not digits.
+const DECIMAL_DIGIT_ZEROS: [u32; 76] = [
+    0x0030, 0x0660, 0x06F0, 0x07C0, 0x0966, 0x09E6, 0x0A66, 0x0AE6, 0x0B66, 0x0BE6, 0x0C66, 0x0CE6,
+    0x0D66, 0x0DE6, 0x0E50, 0x0ED0, 0x0F20, 0x1040, 0x1090, 0x17E0, 0x1810, 0x1946, 0x19D0, 0x1A80,
+    0x1A90, 0x1B50, 0x1BB0, 0x1C40, 0x1C50, 0xA620, 0xA8D0, 0xA900, 0xA9D0, 0xA9F0, 0xAA50, 0xABF0,
+    0xFF10, 0x104A0, 0x10D30, 0x10D40, 0x11066, 0x110F0, 0x11136, 0x111D0, 0x112F0, 0x11450, 0x114D0,
+    0x11650, 0x116C0, 0x116D0, 0x116DA, 0x11730, 0x118E0, 0x11950, 0x11BF0, 0x11C50, 0x11D50, 0x11DA0,
+    0x11F50, 0x16130, 0x16A60, 0x16AC0, 0x16B50, 0x16D70, 0x1CCF0, 0x1D7CE, 0x1D7D8, 0x1D7E2, 0x1D7EC,
+    0x1D7F6, 0x1E140, 0x1E2F0, 0x1E4F0, 0x1E5F1, 0x1E950, 0x1FBF0,
+];
+
+fn is_decimal_number(c: char) -> bool {
+    let c = u32::from(c);
+    // The last run that starts at or before c
+    match DECIMAL_DIGIT_ZEROS.partition_point(|&zero| zero <= c) {
+        0 => false,
+        runs => c - DECIMAL_DIGIT_ZEROS[runs - 1] < 10,
+    }
+}
+
+// The general punctuation block and the CJK punctuation block cover the common non ASCII punctuation
+fn is_unicode_punctuation(c: char) -> bool {
+    matches!(c, '\u{00A1}' | '\u{00A7}' | '\u{00AB}' | '\u{00B6}' | '\u{00B7}' | '\u{00BB}' | '\u{00BF}')
+        || ('\u{2010}'..='\u{2027}').contains(&c)
+        || ('\u{2030}'..='\u{205E}').contains(&c)
+        || ('\u{3001}'..='\u{3003}').contains(&c)
+        || ('\u{3008}'..='\u{3011}').contains(&c)
+}
+
+// Returns the simple (one char to one char) lower and upper case variants of a char.
+// Multi char mappings like 'ß' -> "SS" can not be matched by a single position, so they are skipped.
+pub fn case_variants(c: char) -> impl Iterator<Item = char> {
+    let lower = single_char(c.to_lowercase());
+    let upper = single_char(c.to_uppercase());
+
+    [lower, upper].into_iter().flatten().filter(move |&variant| variant != c)
+}
+
+fn single_char(mut chars: impl Iterator<Item = char>) -> Option<char> {
+    let first = chars.next()?;
+    match chars.next() {
+        Some(_) => None,
+        None => Some(first),
+    }
+}
+
+impl Class {
+    pub fn new(items: Vec<ClassItem>, negated: bool, ignore_case: bool) -> Self {
+        Self {
+            items,
+            negated,
+            ignore_case,
+        }
+    }
+
+    pub fn from_char(c: char, ignore_case: bool) -> Self {
+        Self::new(vec![ClassItem::Range(c, c)], false, ignore_case)
+    }
+
+    pub fn from_named(class: NamedClass, negated: bool) -> Self {
+        Self::new(vec![ClassItem::Named { class, negated }], false, false)
+    }
+
+    pub fn matches(&self, c: char) -> bool {
+        let mut hit = self.matches_exact(c);
+
+        if !hit && self.ignore_case {
+            hit = case_variants(c).any(|variant| self.matches_exact(variant));
+        }
+
+        hit != self.negated
+    }
+
+    fn matches_exact(&self, c: char) -> bool {
+        self.items.iter().any(|item| match item {
+            ClassItem::Range(start, end) => *start <= c && c <= *end,
+            ClassItem::Named { class, negated } => class.matches(c) != *negated,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn class_matches_chars_in_range() {
+        let class = Class::new(vec![ClassItem::Range('a', 'f')], false, false);
+
+        assert!(class.matches('a'));
+        assert!(class.matches('c'));
+        assert!(!class.matches('g'));
+        assert!(!class.matches('A'));
+    }
+
+    #[test]
+    fn negated_class_matches_chars_outside_range() {
+        let class = Class::new(vec![ClassItem::Range('0', '9')], true, false);
+
+        assert!(class.matches('x'));
+        assert!(!class.matches('5'));
+    }
+
+    #[test]
+    fn class_ignores_case_when_enabled() {
+        let class = Class::from_char('é', true);
+
+        assert!(class.matches('É'));
+        assert!(class.matches('é'));
+        assert!(!class.matches('e'));
+    }
+
+    #[test]
+    fn named_classes_are_unicode_aware() {
+        assert!(Class::from_named(NamedClass::Word, false).matches('ö'));
+        assert!(Class::from_named(NamedClass::Space, false).matches('\u{00A0}'));
+        assert!(Class::from_named(NamedClass::DecimalNumber, false).matches('٣'));
+        assert!(Class::from_named(NamedClass::Digit, true).matches('a'));
+    }
+
+    #[test]
+    fn digit_classes_only_match_decimal_digits() {
+        let digit = Class::from_named(NamedClass::Digit, false);
+        assert!(digit.matches('7'));
+        assert!(!digit.matches('٣'));
+
+        let decimal_number = Class::from_named(NamedClass::DecimalNumber, false);
+        assert!(decimal_number.matches('0') && decimal_number.matches('9') && decimal_number.matches('𝟡'));
+        assert!(!decimal_number.matches('/') && !decimal_number.matches(':'));
+
+        // Numbers that are not decimal digits
+        for c in ['½', '²', 'Ⅷ'] {
+            assert!(!digit.matches(c), "{c}");
+            assert!(!decimal_number.matches(c), "{c}");
+        }
+    }
+
+    #[test]
+    fn decimal_digit_zeros_start_runs_of_ten_digits() {
+        assert!(DECIMAL_DIGIT_ZEROS.is_sorted());
+        for zero in DECIMAL_DIGIT_ZEROS {
+            assert!((zero..zero + 10).all(|c| char::from_u32(c).is_some_and(char::is_numeric)), "{zero:X}");
+        }
+    }
+
+    #[test]
+    fn named_class_from_name_accepts_posix_and_unicode_names() {
+        assert_eq!(Some(NamedClass::Alphabetic), NamedClass::from_name("alpha"));
+        assert_eq!(Some(NamedClass::Alphabetic), NamedClass::from_name("L"));
+        assert_eq!(Some(NamedClass::Uppercase), NamedClass::from_name("Lu"));
+        assert_eq!(None, NamedClass::from_name("Klingon"));
+    }
+}
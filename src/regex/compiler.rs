@@ -0,0 +1,244 @@
+use super::class::Class;
+use super::parser::{Assertion, Ast, RegexError};
+
+// The compiled pattern is a Thompson NFA stored as a list of instructions.
+// Both the Pike VM and the lazy DFA run this same program.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inst {
+    Match,
+    // Consumes one char if it is in the class
+    Class(Class),
+    // Consumes any char except a line break
+    Any,
+    // Consumes nothing, but only continues if the assertion holds at the current position
+    Assert(Assertion),
+    // Continues at both targets, where the first one has priority
+    Split(usize, usize),
+    Jmp(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub insts: Vec<Inst>,
+    pub start: usize,
+    // True if the program contains \b, \B or the -w word checks, which the lazy DFA does not support
+    pub has_word_boundary: bool,
+}
+
+// Guards against patterns like (a{1000}){1000} which would otherwise use up all memory
+const MAX_PROGRAM_SIZE: usize = 100_000;
+
+pub fn compile(ast: &Ast) -> Result<Program, RegexError> {
+    let mut compiler = Compiler { insts: Vec::new() };
+
+    compiler.compile(ast)?;
+    compiler.push(Inst::Match)?;
+
+    let has_word_boundary = compiler.insts.iter().any(|inst| {
+        matches!(
+            inst,
+            Inst::Assert(
+                Assertion::WordBoundary
+                    | Assertion::NotWordBoundary
+                    | Assertion::NoWordBefore
+                    | Assertion::NoWordAfter
+            )
+        )
+    });
+
+    Ok(Program {
+        insts: compiler.insts,
+        start: 0,
+        has_word_boundary,
+    })
+}
+
+struct Compiler {
+    insts: Vec<Inst>,
+}
+
+impl Compiler {
+    fn push(&mut self, inst: Inst) -> Result<usize, RegexError> {
+        if self.insts.len() >= MAX_PROGRAM_SIZE {
+            return Err(RegexError::new("Pattern compiles to a program that is too large", 0));
+        }
+
+        self.insts.push(inst);
+        Ok(self.insts.len() - 1)
+    }
+
+    // Split and Jmp targets are not always known when they are pushed, so they are patched afterwards
+    fn patch(&mut self, index: usize, inst: Inst) {
+        self.insts[index] = inst;
+    }
+
+    fn compile(&mut self, ast: &Ast) -> Result<(), RegexError> {
+        match ast {
+            Ast::Empty => {}
+            Ast::Class(class) => {
+                self.push(Inst::Class(class.clone()))?;
+            }
+            Ast::Dot => {
+                self.push(Inst::Any)?;
+            }
+            Ast::Assertion(assertion) => {
+                self.push(Inst::Assert(*assertion))?;
+            }
+            Ast::Group(ast) => self.compile(ast)?,
+            Ast::Concat(items) => {
+                for item in items {
+                    self.compile(item)?;
+                }
+            }
+            Ast::Alternate(branches) => self.compile_alternate(branches)?,
+            Ast::Repeat {
+                ast,
+                min,
+                max,
+                greedy,
+            } => self.compile_repeat(ast, *min, *max, *greedy)?,
+        }
+
+        Ok(())
+    }
+
+    // a|b|c compiles to:
+    //     split L1, L2
+    // L1: a
+    //     jmp END
+    // L2: split L3, L4
+    // L3: b
+    //     jmp END
+    // L4: c
+    // END:
+    fn compile_alternate(&mut self, branches: &[Ast]) -> Result<(), RegexError> {
+        let mut jumps_to_end = Vec::new();
+
+        for (i, branch) in branches.iter().enumerate() {
+            if i == branches.len() - 1 {
+                self.compile(branch)?;
+                break;
+            }
+
+            let split = self.push(Inst::Split(0, 0))?;
+            self.compile(branch)?;
+            jumps_to_end.push(self.push(Inst::Jmp(0))?);
+
+            let next_branch = self.insts.len();
+            self.patch(split, Inst::Split(split + 1, next_branch));
+        }
+
+        let end = self.insts.len();
+        for jump in jumps_to_end {
+            self.patch(jump, Inst::Jmp(end));
+        }
+
+        Ok(())
+    }
+
+    // a{2,4} is compiled as "a a a? a?" and a{2,} as "a a a*"
+    fn compile_repeat(&mut self, ast: &Ast, min: u32, max: Option<u32>, greedy: bool) -> Result<(), RegexError> {
+        for _ in 0..min {
+            self.compile(ast)?;
+        }
+
+        match max {
+            None => {
+                // L1: split L2, END (swapped when lazy)
+                // L2: a
+                //     jmp L1
+                // END:
+                let split = self.push(Inst::Split(0, 0))?;
+                self.compile(ast)?;
+                self.push(Inst::Jmp(split))?;
+                let end = self.insts.len();
+                self.patch(split, self.split(split + 1, end, greedy));
+            }
+            Some(max) => {
+                // Nesting the optional copies as "(a(a)?)?" instead of "a?a?" means that
+                // a failed optional copy skips all the remaining ones at once
+                let mut splits = Vec::new();
+                for _ in min..max {
+                    splits.push(self.push(Inst::Split(0, 0))?);
+                    self.compile(ast)?;
+                }
+                let end = self.insts.len();
+                for split in splits {
+                    self.patch(split, self.split(split + 1, end, greedy));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn split(&self, body: usize, skip: usize, greedy: bool) -> Inst {
+        if greedy {
+            Inst::Split(body, skip)
+        } else {
+            Inst::Split(skip, body)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regex::parser::parse;
+
+    fn literal(c: char) -> Inst {
+        Inst::Class(Class::from_char(c, false))
+    }
+
+    #[test]
+    fn compile_alternation_uses_split_and_jumps() {
+        let program = compile(&parse("a|b", false).unwrap()).unwrap();
+
+        assert_eq!(
+            vec![
+                Inst::Split(1, 3),
+                literal('a'),
+                Inst::Jmp(4),
+                literal('b'),
+                Inst::Match
+            ],
+            program.insts
+        )
+    }
+
+    #[test]
+    fn compile_lazy_star_prefers_skipping() {
+        let program = compile(&parse("a*?", false).unwrap()).unwrap();
+
+        assert_eq!(
+            vec![Inst::Split(3, 1), literal('a'), Inst::Jmp(0), Inst::Match],
+            program.insts
+        )
+    }
+
+    #[test]
+    fn compile_counted_repetition_expands_copies() {
+        let program = compile(&parse("a{1,2}", false).unwrap()).unwrap();
+
+        assert_eq!(
+            vec![literal('a'), Inst::Split(2, 3), literal('a'), Inst::Match],
+            program.insts
+        )
+    }
+
+    #[test]
+    fn compile_detects_word_boundaries() {
+        let with_boundary = compile(&parse(r"\bword\b", false).unwrap()).unwrap();
+        let without_boundary = compile(&parse("^word$", false).unwrap()).unwrap();
+
+        assert!(with_boundary.has_word_boundary);
+        assert!(!without_boundary.has_word_boundary);
+    }
+
+    #[test]
+    fn compile_fails_when_program_is_too_large() {
+        let result = compile(&parse("(((a{1000}){1000}){1000})", false).unwrap());
+
+        assert!(result.is_err())
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+
+use super::compiler::{Inst, Program};
+use super::decode_char;
+use super::parser::Assertion;
+
+// The lazy DFA answers "does this line match anywhere?" without tracking where.
+// Each DFA state is the set of NFA instructions that are alive at a position,
+// and states and transitions are only built the first time the text needs them.
+// It does not support \b and \B, so those programs always use the Pike VM.
+pub struct LazyDfa {
+    states: Vec<State>,
+    cache: HashMap<Vec<usize>, usize>,
+    start: Option<usize>,
+}
+
+struct State {
+    // The NFA instructions that consume a char, plus Match and pending $ assertions
+    insts: Vec<usize>,
+    is_match: bool,
+    // True if the state matches when the line ends right here, through a pending $
+    is_match_at_end: bool,
+    // Transitions for ASCII chars are stored in a table since they are by far the most common
+    ascii: [Option<usize>; 128],
+    other: HashMap<char, usize>,
+}
+
+// When a pattern makes the DFA build more states than this, the cache is cleared and rebuilt as needed
+const MAX_STATES: usize = 4096;
+
+impl LazyDfa {
+    pub fn new() -> Self {
+        Self {
+            states: Vec::new(),
+            cache: HashMap::new(),
+            start: None,
+        }
+    }
+
+    pub fn is_match(&mut self, program: &Program, haystack: &[u8]) -> bool {
+        self.shortest_match_end(program, haystack, 0).is_some()
+    }
+
+    // Returns the smallest end of all the matches starting at or after start, which is where the
+    // DFA first reaches a match state. start has to be the start of a line, like the start state is.
+    pub fn shortest_match_end(&mut self, program: &Program, haystack: &[u8], start: usize) -> Option<usize> {
+        let mut state = self.start_state(program);
+        let mut at = start;
+
+        while let Some((c, len)) = decode_char(haystack, at) {
+            if self.states[state].is_match || (c == '\n' && self.states[state].is_match_at_end) {
+                return Some(at);
+            }
+
+            state = self.next_state(program, state, c);
+            at += len;
+        }
+
+        (self.states[state].is_match || self.states[state].is_match_at_end).then_some(at)
+    }
+
+    fn start_state(&mut self, program: &Program) -> usize {
+        if let Some(start) = self.start {
+            return start;
+        }
+
+        let insts = closure(program, &[program.start], true);
+        let start = self.add_state(program, insts);
+        self.start = Some(start);
+        start
+    }
+
+    fn next_state(&mut self, program: &Program, state: usize, c: char) -> usize {
+        let known = if c.is_ascii() {
+            self.states[state].ascii[c as usize]
+        } else {
+            self.states[state].other.get(&c).copied()
+        };
+        if let Some(next) = known {
+            return next;
+        }
+
+        // Every instruction that consumes c moves on to the next instruction,
+        // and a new match attempt is started at every position, since the search is unanchored
+        let mut seeds: Vec<usize> = self.states[state]
+            .insts
+            .iter()
+            .filter(|&&pc| match &program.insts[pc] {
+                Inst::Class(class) => class.matches(c),
+                Inst::Any => c != '\n',
+                _ => false,
+            })
+            .map(|&pc| pc + 1)
+            .collect();
+        seeds.push(program.start);
+
+        let insts = closure(program, &seeds, c == '\n');
+
+        if self.states.len() >= MAX_STATES && !self.cache.contains_key(&insts) {
+            self.states.clear();
+            self.cache.clear();
+            self.start = None;
+            // The old state is gone, so the transition is not recorded
+            return self.add_state(program, insts);
+        }
+
+        let next = self.add_state(program, insts);
+        if c.is_ascii() {
+            self.states[state].ascii[c as usize] = Some(next);
+        } else {
+            self.states[state].other.insert(c, next);
+        }
+        next
+    }
+
+    fn add_state(&mut self, program: &Program, insts: Vec<usize>) -> usize {
+        if let Some(&state) = self.cache.get(&insts) {
+            return state;
+        }
+
+        let is_match = insts.iter().any(|&pc| program.insts[pc] == Inst::Match);
+
+        let pending_ends: Vec<usize> = insts
+            .iter()
+            .filter(|&&pc| program.insts[pc] == Inst::Assert(Assertion::EndLine))
+            .map(|&pc| pc + 1)
+            .collect();
+        let is_match_at_end = closure_at_end(program, &pending_ends);
+
+        self.states.push(State {
+            insts: insts.clone(),
+            is_match,
+            is_match_at_end,
+            ascii: [None; 128],
+            other: HashMap::new(),
+        });
+        self.cache.insert(insts, self.states.len() - 1);
+        self.states.len() - 1
+    }
+}
+
+// Follows jumps, splits and assertions from the seeds and returns the sorted set of
+// instructions that are reached. $ can not be decided until the next char is known,
+// so those assertions are kept in the set as pending.
+fn closure(program: &Program, seeds: &[usize], at_line_start: bool) -> Vec<usize> {
+    let mut visited = vec![false; program.insts.len()];
+    let mut stack: Vec<usize> = seeds.to_vec();
+    let mut insts = Vec::new();
+
+    while let Some(pc) = stack.pop() {
+        if visited[pc] {
+            continue;
+        }
+        visited[pc] = true;
+
+        match &program.insts[pc] {
+            Inst::Jmp(target) => stack.push(*target),
+            Inst::Split(first, second) => {
+                stack.push(*first);
+                stack.push(*second);
+            }
+            Inst::Assert(Assertion::StartLine) => {
+                if at_line_start {
+                    stack.push(pc + 1);
+                }
+            }
+            // Word boundaries never reach the DFA, and $ is kept pending
+            Inst::Assert(_) | Inst::Match | Inst::Class(_) | Inst::Any => insts.push(pc),
+        }
+    }
+
+    insts.sort_unstable();
+    insts
+}
+
+// Checks if a Match can be reached from the seeds when the line ends, where every $ holds
+// and nothing more can be consumed
+fn closure_at_end(program: &Program, seeds: &[usize]) -> bool {
+    let mut visited = vec![false; program.insts.len()];
+    let mut stack: Vec<usize> = seeds.to_vec();
+
+    while let Some(pc) = stack.pop() {
+        if visited[pc] {
+            continue;
+        }
+        visited[pc] = true;
+
+        match &program.insts[pc] {
+            Inst::Match => return true,
+            Inst::Jmp(target) => stack.push(*target),
+            Inst::Split(first, second) => {
+                stack.push(*first);
+                stack.push(*second);
+            }
+            Inst::Assert(Assertion::EndLine) => stack.push(pc + 1),
+            _ => {}
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regex::compiler::compile;
+    use crate::regex::parser::parse;
+
+    fn is_match(pattern: &str, text: &str) -> bool {
+        let program = compile(&parse(pattern, false).unwrap()).unwrap();
+        LazyDfa::new().is_match(&program, text.as_bytes())
+    }
+
+    #[test]
+    fn dfa_matches_anywhere_in_text() {
+        assert!(is_match("b+c", "aaabbbc"));
+        assert!(!is_match("b+c", "aaabbb"));
+    }
+
+    #[test]
+    fn dfa_respects_start_anchor() {
+        assert!(is_match("^ab", "abc"));
+        assert!(!is_match("^bc", "abc"));
+    }
+
+    #[test]
+    fn dfa_respects_end_anchor() {
+        assert!(is_match("bc$", "abc"));
+        assert!(!is_match("ab$", "abc"));
+        assert!(is_match("^$", ""));
+        assert!(is_match("c$|x", "abc"));
+    }
+
+    #[test]
+    fn dfa_matches_unicode_classes() {
+        assert!(is_match(r"\p{Lu}\d", "überΩ7"));
+        assert!(!is_match(r"\p{Lu}\d", "über7"));
+    }
+
+    #[test]
+    fn dfa_reuses_states_across_searches() {
+        let program = compile(&parse("a|b", false).unwrap()).unwrap();
+        let mut dfa = LazyDfa::new();
+
+        assert!(dfa.is_match(&program, b"xxb"));
+        let state_count = dfa.states.len();
+        assert!(dfa.is_match(&program, b"xxb"));
+
+        assert_eq!(state_count, dfa.states.len())
+    }
+}
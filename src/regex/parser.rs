@@ -0,0 +1,619 @@
+use std::fmt;
+
+use super::class::{Class, ClassItem, NamedClass};
+
+// The abstract syntax tree a pattern is parsed into before it is compiled
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ast {
+    Empty,
+    Class(Class),
+    Dot,
+    Assertion(Assertion),
+    Group(Box<Ast>),
+    Repeat {
+        ast: Box<Ast>,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    },
+    Concat(Vec<Ast>),
+    Alternate(Vec<Ast>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Assertion {
+    StartLine,
+    EndLine,
+    WordBoundary,
+    NotWordBoundary,
+    // No word char right before or right after, which is what -w asks of both ends of a match.
+    // Unlike \b these also hold next to a non word char, like in front of the - of -x.
+    // There is no syntax for them, they are only put around a whole pattern by Regex::whole_words.
+    NoWordBefore,
+    NoWordAfter,
+}
+
+// Repetitions like a{1000}{1000} would blow up the compiled program, so counted repetitions are capped
+const MAX_REPEAT: u32 = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegexError {
+    pub message: String,
+    // Char position in the pattern where the error was detected
+    pub position: usize,
+}
+
+impl RegexError {
+    pub fn new(message: &str, position: usize) -> Self {
+        Self {
+            message: String::from(message),
+            position,
+        }
+    }
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for RegexError {}
+
+pub fn parse(pattern: &str, ignore_case: bool) -> Result<Ast, RegexError> {
+    let mut parser = Parser {
+        chars: pattern.chars().collect(),
+        position: 0,
+        ignore_case,
+    };
+
+    let ast = parser.parse_alternation()?;
+
+    // The only way parse_alternation stops before the end is an unmatched ')'
+    if parser.position < parser.chars.len() {
+        return Err(RegexError::new("Unmatched ')'", parser.position));
+    }
+
+    Ok(ast)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    // Can be switched on inside the pattern with (?i)
+    ignore_case: bool,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn peek_is(&self, expected: &str) -> bool {
+        let expected: Vec<char> = expected.chars().collect();
+        self.chars[self.position..].starts_with(&expected)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.position += 1;
+        }
+        c
+    }
+
+    fn error(&self, message: &str) -> RegexError {
+        RegexError::new(message, self.position)
+    }
+
+    // alternation := concat ('|' concat)*
+    fn parse_alternation(&mut self) -> Result<Ast, RegexError> {
+        let mut branches = vec![self.parse_concat()?];
+
+        while self.peek() == Some('|') {
+            self.position += 1;
+            branches.push(self.parse_concat()?);
+        }
+
+        if branches.len() == 1 {
+            Ok(branches.remove(0))
+        } else {
+            Ok(Ast::Alternate(branches))
+        }
+    }
+
+    // concat := repeat*
+    fn parse_concat(&mut self) -> Result<Ast, RegexError> {
+        let mut items = Vec::new();
+
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+
+            // (?i) only changes flags and does not add anything to the tree
+            if self.peek_is("(?i)") {
+                self.position += 4;
+                self.ignore_case = true;
+                continue;
+            }
+
+            items.push(self.parse_repeat()?);
+        }
+
+        match items.len() {
+            0 => Ok(Ast::Empty),
+            1 => Ok(items.remove(0)),
+            _ => Ok(Ast::Concat(items)),
+        }
+    }
+
+    // repeat := atom ('*' | '+' | '?' | '{n}' | '{n,}' | '{n,m}')* with an optional '?' for lazy
+    fn parse_repeat(&mut self) -> Result<Ast, RegexError> {
+        let mut ast = self.parse_atom()?;
+
+        loop {
+            let start = self.position;
+            let (min, max) = match self.peek() {
+                Some('*') => {
+                    self.position += 1;
+                    (0, None)
+                }
+                Some('+') => {
+                    self.position += 1;
+                    (1, None)
+                }
+                Some('?') => {
+                    self.position += 1;
+                    (0, Some(1))
+                }
+                Some('{') => match self.parse_counted_repeat()? {
+                    Some(bounds) => bounds,
+                    None => break, // Not a valid repetition, so the '{' is parsed as a literal next
+                },
+                _ => break,
+            };
+
+            if let Ast::Empty | Ast::Assertion(_) = ast {
+                return Err(RegexError::new("Repetition operator missing expression", start));
+            }
+
+            let greedy = if self.peek() == Some('?') {
+                self.position += 1;
+                false
+            } else {
+                true
+            };
+
+            ast = Ast::Repeat {
+                ast: Box::new(ast),
+                min,
+                max,
+                greedy,
+            };
+        }
+
+        Ok(ast)
+    }
+
+    // Returns None without consuming anything if the braces are not a repetition, like in "a{b}"
+    fn parse_counted_repeat(&mut self) -> Result<Option<(u32, Option<u32>)>, RegexError> {
+        let start = self.position;
+        self.position += 1; // Skip '{'
+
+        let min = match self.parse_number() {
+            Some(min) => min,
+            None => {
+                self.position = start;
+                return Ok(None);
+            }
+        };
+
+        let max = if self.peek() == Some(',') {
+            self.position += 1;
+            if self.peek() == Some('}') {
+                None
+            } else {
+                match self.parse_number() {
+                    Some(max) => Some(max),
+                    None => {
+                        self.position = start;
+                        return Ok(None);
+                    }
+                }
+            }
+        } else {
+            Some(min)
+        };
+
+        if self.next() != Some('}') {
+            self.position = start;
+            return Ok(None);
+        }
+
+        if min > MAX_REPEAT || max.is_some_and(|max| max > MAX_REPEAT) {
+            return Err(RegexError::new("Repetition count too large", start));
+        }
+        if max.is_some_and(|max| max < min) {
+            return Err(RegexError::new("Invalid repetition range", start));
+        }
+
+        Ok(Some((min, max)))
+    }
+
+    fn parse_number(&mut self) -> Option<u32> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+
+        let digits: String = self.chars[start..self.position].iter().collect();
+        // Numbers too big for u32 are reported as too large by the caller through MAX_REPEAT
+        digits.parse::<u64>().ok().map(|n| n.min(u32::MAX as u64) as u32)
+    }
+
+    fn parse_atom(&mut self) -> Result<Ast, RegexError> {
+        let start = self.position;
+
+        match self.next() {
+            Some('(') => self.parse_group(start),
+            Some('[') => self.parse_class(start),
+            Some('.') => Ok(Ast::Dot),
+            Some('^') => Ok(Ast::Assertion(Assertion::StartLine)),
+            Some('$') => Ok(Ast::Assertion(Assertion::EndLine)),
+            Some('\\') => self.parse_escape(start),
+            Some(c @ ('*' | '+' | '?')) => Err(RegexError::new(
+                &format!("Repetition operator '{c}' missing expression"),
+                start,
+            )),
+            Some(c) => Ok(Ast::Class(Class::from_char(c, self.ignore_case))),
+            None => Err(self.error("Unexpected end of pattern")),
+        }
+    }
+
+    fn parse_group(&mut self, start: usize) -> Result<Ast, RegexError> {
+        // Flags only apply inside the group they are set in
+        let outer_ignore_case = self.ignore_case;
+
+        if self.peek_is("?:") {
+            self.position += 2;
+        } else if self.peek_is("?i:") {
+            self.position += 3;
+            self.ignore_case = true;
+        } else if self.peek() == Some('?') {
+            return Err(self.error("Unsupported group flag"));
+        }
+
+        let ast = self.parse_alternation()?;
+
+        if self.next() != Some(')') {
+            return Err(RegexError::new("Unclosed group", start));
+        }
+
+        self.ignore_case = outer_ignore_case;
+
+        Ok(Ast::Group(Box::new(ast)))
+    }
+
+    fn parse_class(&mut self, start: usize) -> Result<Ast, RegexError> {
+        let negated = if self.peek() == Some('^') {
+            self.position += 1;
+            true
+        } else {
+            false
+        };
+
+        let mut items = Vec::new();
+
+        // A ']' right after the opening bracket is a literal, like in "[]a]"
+        if self.peek() == Some(']') {
+            self.position += 1;
+            items.push(ClassItem::Range(']', ']'));
+        }
+
+        loop {
+            let item_start = self.position;
+            let c = match self.next() {
+                Some(']') => break,
+                Some(c) => c,
+                None => return Err(RegexError::new("Unclosed character class", start)),
+            };
+
+            if c == '[' && self.peek() == Some(':') {
+                items.push(self.parse_posix_class(item_start)?);
+                continue;
+            }
+
+            let first = if c == '\\' {
+                match self.parse_class_escape(item_start)? {
+                    ClassEscape::Char(c) => c,
+                    ClassEscape::Item(item) => {
+                        items.push(item);
+                        continue;
+                    }
+                }
+            } else {
+                c
+            };
+
+            // A '-' is a range unless it is the last char in the class, like in "[a-]"
+            if self.peek() == Some('-') && self.chars.get(self.position + 1) != Some(&']') {
+                self.position += 1;
+                let last = match self.next() {
+                    Some('\\') => match self.parse_class_escape(self.position - 1)? {
+                        ClassEscape::Char(c) => c,
+                        ClassEscape::Item(_) => {
+                            return Err(RegexError::new("Invalid range in character class", item_start));
+                        }
+                    },
+                    Some(c) => c,
+                    None => return Err(RegexError::new("Unclosed character class", start)),
+                };
+
+                if last < first {
+                    return Err(RegexError::new("Invalid range in character class", item_start));
+                }
+                items.push(ClassItem::Range(first, last));
+            } else {
+                items.push(ClassItem::Range(first, first));
+            }
+        }
+
+        Ok(Ast::Class(Class::new(items, negated, self.ignore_case)))
+    }
+
+    // Parses [:name:] and [:^name:] inside a bracket class. The leading '[' is already consumed.
+    fn parse_posix_class(&mut self, start: usize) -> Result<ClassItem, RegexError> {
+        self.position += 1; // Skip ':'
+
+        let negated = if self.peek() == Some('^') {
+            self.position += 1;
+            true
+        } else {
+            false
+        };
+
+        let name_start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.position += 1;
+        }
+        let name: String = self.chars[name_start..self.position].iter().collect();
+
+        if !self.peek_is(":]") {
+            return Err(RegexError::new("Unclosed POSIX character class", start));
+        }
+        self.position += 2;
+
+        match NamedClass::from_name(&name) {
+            Some(class) => Ok(ClassItem::Named { class, negated }),
+            None => Err(RegexError::new("Unknown POSIX character class", start)),
+        }
+    }
+
+    fn parse_class_escape(&mut self, start: usize) -> Result<ClassEscape, RegexError> {
+        match self.parse_escape(start)? {
+            Ast::Class(class) if class.items.len() == 1 && !class.negated => match class.items[0].clone() {
+                ClassItem::Range(c, _) => Ok(ClassEscape::Char(c)),
+                item => Ok(ClassEscape::Item(item)),
+            },
+            _ => Err(RegexError::new("Escape not allowed in character class", start)),
+        }
+    }
+
+    // Parses what comes after a '\'. The '\' itself is already consumed.
+    fn parse_escape(&mut self, start: usize) -> Result<Ast, RegexError> {
+        let c = match self.next() {
+            Some(c) => c,
+            None => return Err(RegexError::new("Pattern ends with a trailing '\\'", start)),
+        };
+
+        let named = |class: NamedClass, negated: bool| Ok(Ast::Class(Class::from_named(class, negated)));
+        let literal = |c: char, ignore_case: bool| Ok(Ast::Class(Class::from_char(c, ignore_case)));
+
+        match c {
+            'd' => named(NamedClass::Digit, false),
+            'D' => named(NamedClass::Digit, true),
+            'w' => named(NamedClass::Word, false),
+            'W' => named(NamedClass::Word, true),
+            's' => named(NamedClass::Space, false),
+            'S' => named(NamedClass::Space, true),
+            'b' => Ok(Ast::Assertion(Assertion::WordBoundary)),
+            'B' => Ok(Ast::Assertion(Assertion::NotWordBoundary)),
+            'p' | 'P' => {
+                let class = self.parse_unicode_class_name(start)?;
+                named(class, c == 'P')
+            }
+            'n' => literal('\n', false),
+            't' => literal('\t', false),
+            'r' => literal('\r', false),
+            'f' => literal('\x0C', false),
+            'v' => literal('\x0B', false),
+            '0' => literal('\0', false),
+            'x' => {
+                let c = self.parse_hex_escape(start)?;
+                literal(c, self.ignore_case)
+            }
+            c if c.is_ascii_alphanumeric() => Err(RegexError::new(&format!("Unknown escape '\\{c}'"), start)),
+            // Any other escaped char, like \. or \\, is a literal
+            c => literal(c, self.ignore_case),
+        }
+    }
+
+    // Parses the name in \pL or \p{Letter}
+    fn parse_unicode_class_name(&mut self, start: usize) -> Result<NamedClass, RegexError> {
+        let name: String = if self.peek() == Some('{') {
+            self.position += 1;
+            let name_start = self.position;
+            while self.peek().is_some_and(|c| c != '}') {
+                self.position += 1;
+            }
+            if self.next() != Some('}') {
+                return Err(RegexError::new("Unclosed Unicode class name", start));
+            }
+            self.chars[name_start..self.position - 1].iter().collect()
+        } else {
+            match self.next() {
+                Some(c) => c.to_string(),
+                None => return Err(RegexError::new("Missing Unicode class name", start)),
+            }
+        };
+
+        NamedClass::from_name(&name)
+            .ok_or_else(|| RegexError::new(&format!("Unknown Unicode class '{name}'"), start))
+    }
+
+    // Parses \xHH and \x{HHHH}
+    fn parse_hex_escape(&mut self, start: usize) -> Result<char, RegexError> {
+        let digits: String = if self.peek() == Some('{') {
+            self.position += 1;
+            let digits_start = self.position;
+            while self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+                self.position += 1;
+            }
+            let digits = self.chars[digits_start..self.position].iter().collect();
+            if self.next() != Some('}') {
+                return Err(RegexError::new("Unclosed hex escape", start));
+            }
+            digits
+        } else {
+            let digits_start = self.position;
+            while self.position < digits_start + 2 && self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+                self.position += 1;
+            }
+            self.chars[digits_start..self.position].iter().collect()
+        };
+
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| RegexError::new("Invalid hex escape", start))
+    }
+}
+
+enum ClassEscape {
+    Char(char),
+    Item(ClassItem),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn literal(c: char) -> Ast {
+        Ast::Class(Class::from_char(c, false))
+    }
+
+    #[test]
+    fn parse_concatenates_literals() {
+        let ast = parse("ab", false).unwrap();
+
+        assert_eq!(Ast::Concat(vec![literal('a'), literal('b')]), ast)
+    }
+
+    #[test]
+    fn parse_alternation_has_lowest_precedence() {
+        let ast = parse("ab|c", false).unwrap();
+
+        assert_eq!(
+            Ast::Alternate(vec![Ast::Concat(vec![literal('a'), literal('b')]), literal('c')]),
+            ast
+        )
+    }
+
+    #[test]
+    fn parse_repetition_binds_to_previous_atom() {
+        let ast = parse("ab*?", false).unwrap();
+
+        assert_eq!(
+            Ast::Concat(vec![
+                literal('a'),
+                Ast::Repeat {
+                    ast: Box::new(literal('b')),
+                    min: 0,
+                    max: None,
+                    greedy: false
+                }
+            ]),
+            ast
+        )
+    }
+
+    #[test]
+    fn parse_counted_repetition() {
+        let ast = parse("a{2,5}", false).unwrap();
+
+        assert_eq!(
+            Ast::Repeat {
+                ast: Box::new(literal('a')),
+                min: 2,
+                max: Some(5),
+                greedy: true
+            },
+            ast
+        )
+    }
+
+    #[test]
+    fn parse_brace_without_count_is_literal() {
+        let ast = parse("a{b}", false).unwrap();
+
+        assert_eq!(
+            Ast::Concat(vec![literal('a'), literal('{'), literal('b'), literal('}')]),
+            ast
+        )
+    }
+
+    #[test]
+    fn parse_class_with_ranges_and_escapes() {
+        let ast = parse(r"[^a-c\d_]", false).unwrap();
+
+        assert_eq!(
+            Ast::Class(Class::new(
+                vec![
+                    ClassItem::Range('a', 'c'),
+                    ClassItem::Named {
+                        class: NamedClass::Digit,
+                        negated: false
+                    },
+                    ClassItem::Range('_', '_'),
+                ],
+                true,
+                false
+            )),
+            ast
+        )
+    }
+
+    #[test]
+    fn parse_inline_ignore_case_flag() {
+        let ast = parse("(?i)a", false).unwrap();
+
+        assert_eq!(Ast::Class(Class::from_char('a', true)), ast)
+    }
+
+    #[test]
+    fn parse_fails_on_unclosed_group_with_position() {
+        let error = parse("ab(cd", false).unwrap_err();
+
+        assert_eq!(RegexError::new("Unclosed group", 2), error)
+    }
+
+    #[test]
+    fn parse_fails_on_unmatched_closing_paren() {
+        let error = parse("ab)", false).unwrap_err();
+
+        assert_eq!(2, error.position)
+    }
+
+    #[test]
+    fn parse_fails_on_repetition_without_expression() {
+        assert!(parse("*a", false).is_err());
+        assert!(parse("a|+", false).is_err());
+        assert!(parse("^*", false).is_err());
+    }
+
+    #[test]
+    fn parse_fails_on_unknown_escape() {
+        assert!(parse(r"\q", false).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use super::compiler::{Inst, Program};
+use super::{assertion_holds, decode_char};
+
+// The Pike VM runs all NFA threads in lockstep over the text, one char at a time.
+// Threads are kept in priority order, which gives the same leftmost-first matches
+// a backtracking engine would find, but in linear time.
+#[derive(Clone, Copy)]
+struct Thread {
+    pc: usize,
+    // Byte offset where the match this thread is working on started
+    start: usize,
+}
+
+// A sparse set keeps insertion order (which is the thread priority) and still allows
+// constant time membership checks and clearing
+struct ThreadList {
+    sparse: Vec<usize>,
+    dense: Vec<Thread>,
+}
+
+impl ThreadList {
+    fn new(size: usize) -> Self {
+        Self {
+            sparse: vec![0; size],
+            dense: Vec::with_capacity(size),
+        }
+    }
+
+    fn contains(&self, pc: usize) -> bool {
+        let index = self.sparse[pc];
+        index < self.dense.len() && self.dense[index].pc == pc
+    }
+
+    fn insert(&mut self, thread: Thread) {
+        self.sparse[thread.pc] = self.dense.len();
+        self.dense.push(thread);
+    }
+
+    fn clear(&mut self) {
+        self.dense.clear();
+    }
+}
+
+// Finds the leftmost-first match that starts at or after the byte offset start.
+// Returns the byte range of the match.
+pub fn find_at(program: &Program, haystack: &[u8], start: usize) -> Option<(usize, usize)> {
+    let mut current = ThreadList::new(program.insts.len());
+    let mut next = ThreadList::new(program.insts.len());
+    let mut stack = Vec::new();
+
+    let mut matched = None;
+    let mut at = start;
+
+    loop {
+        // Start a new attempt at every position until a match is found.
+        // It is added last, so it has the lowest priority of all threads.
+        if matched.is_none() {
+            add_thread(program, &mut current, &mut stack, program.start, at, haystack, at);
+        }
+
+        if current.dense.is_empty() {
+            break;
+        }
+
+        let next_char = decode_char(haystack, at);
+
+        for i in 0..current.dense.len() {
+            let thread = current.dense[i];
+
+            let consumes = match (&program.insts[thread.pc], next_char) {
+                (Inst::Match, _) => {
+                    matched = Some((thread.start, at));
+                    // Every thread after this one has lower priority, so they are cut off
+                    break;
+                }
+                (Inst::Class(class), Some((c, _))) => class.matches(c),
+                (Inst::Any, Some((c, _))) => c != '\n',
+                _ => false,
+            };
+
+            if let (true, Some((_, len))) = (consumes, next_char) {
+                add_thread(program, &mut next, &mut stack, thread.pc + 1, thread.start, haystack, at + len);
+            }
+        }
+
+        match next_char {
+            Some((_, len)) => at += len,
+            None => break,
+        }
+
+        std::mem::swap(&mut current, &mut next);
+        next.clear();
+    }
+
+    matched
+}
+
+// Follows all the instructions that do not consume a char (jumps, splits and assertions)
+// and adds the threads they lead to in priority order.
+// An explicit stack is used instead of recursion since programs can be very large.
+fn add_thread(
+    program: &Program,
+    list: &mut ThreadList,
+    stack: &mut Vec<usize>,
+    pc: usize,
+    start: usize,
+    haystack: &[u8],
+    at: usize,
+) {
+    stack.push(pc);
+
+    while let Some(pc) = stack.pop() {
+        if list.contains(pc) {
+            continue;
+        }
+        list.insert(Thread { pc, start });
+
+        match &program.insts[pc] {
+            Inst::Jmp(target) => stack.push(*target),
+            Inst::Split(first, second) => {
+                // Pushed in reverse so the first target is followed first
+                stack.push(*second);
+                stack.push(*first);
+            }
+            Inst::Assert(assertion) if assertion_holds(*assertion, haystack, at) => stack.push(pc + 1),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regex::compiler::compile;
+    use crate::regex::parser::parse;
+
+    fn find(pattern: &str, text: &str) -> Option<(usize, usize)> {
+        let program = compile(&parse(pattern, false).unwrap()).unwrap();
+        find_at(&program, text.as_bytes(), 0)
+    }
+
+    #[test]
+    fn find_returns_leftmost_match() {
+        assert_eq!(Some((4, 7)), find("b.d", "abc bcd bed"))
+    }
+
+    #[test]
+    fn find_prefers_first_alternative() {
+        assert_eq!(Some((0, 3)), find("abc|abcdef", "abcdef"))
+    }
+
+    #[test]
+    fn find_greedy_and_lazy_repetition() {
+        assert_eq!(Some((0, 6)), find("a.*c", "abcabc"));
+        assert_eq!(Some((0, 3)), find("a.*?c", "abcabc"));
+    }
+
+    #[test]
+    fn find_respects_anchors() {
+        assert_eq!(None, find("^b", "ab"));
+        assert_eq!(Some((1, 2)), find("b$", "ab"));
+        assert_eq!(Some((0, 0)), find("^", "ab"));
+    }
+
+    #[test]
+    fn find_respects_word_boundaries() {
+        assert_eq!(Some((4, 6)), find(r"\bto\b", "too to"));
+        assert_eq!(Some((0, 2)), find(r"to\B", "too to"));
+    }
+
+    #[test]
+    fn find_returns_byte_offsets_for_multibyte_chars() {
+        assert_eq!(Some((2, 6)), find("b.c", "åbéc"))
+    }
+
+    #[test]
+    fn find_handles_nested_empty_loops() {
+        assert_eq!(Some((0, 2)), find("(a*)*b", "ab"))
+    }
+}
@@ -1,16 +1,966 @@
-use std::{error::Error, fs};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, mpsc};
+use std::thread;
+use std::time::Instant;
 
-use crate::config::Config;
-use crate::model::Content;
+use crate::config::{BinaryFiles, Config};
+use crate::display::{display_binary_match, display_count, display_path, display_separator};
+use crate::encoding::{DecodingReader, Encoding, sniff_bom};
+use crate::error::MinigrepError;
+use crate::gzip::{GzipReader, is_gzip};
+use crate::json;
+use crate::matcher::Matcher;
+use crate::mmap::Mmap;
+use crate::model::LineKind;
+use crate::reader::{CandidateLines, LineReader, LineSource};
+use crate::search::Search;
+use crate::stats::Stats;
+use crate::tar::{EntryKind, TarArchive, is_tar};
+use crate::walk::{Walk, WalkError};
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let text = fs::read_to_string(&config.file_path)?;
+// The name grep uses for standard input in its output
+const STDIN_NAME: &str = "(standard input)";
 
-    let content = Content::from_str(&text);
-    let searched_content= content.search(&config.query, config.ignore_case, config.only_match_words, config.inverted_match);
-    let highlighted_content = searched_content.highlight(&config.query, config.ignore_case);
+// Files at least this big are memory mapped and scanned as a whole. For smaller files setting up
+// the map costs more than reading them does.
+const MMAP_THRESHOLD: u64 = 8 * 1024 * 1024;
 
-    highlighted_content.display(config.show_line_numbers);
+// A file with a NUL byte in this many leading bytes is taken to be binary, like grep does
+const BINARY_BLOCK_SIZE: usize = 8 * 1024;
+
+// How a run ended, which decides the exit code like in grep
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Outcome {
+    Matched,
+    NotMatched,
+    // A file could not be searched, which counts as an error even if other files matched
+    FileErrors,
+}
+
+impl Outcome {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Outcome::Matched => 0,
+            Outcome::NotMatched => 1,
+            Outcome::FileErrors => 2,
+        }
+    }
+}
+
+pub fn run(config: Config) -> Result<Outcome, MinigrepError> {
+    run_with_output(config, &mut io::stdout().lock(), &mut io::stderr())
+}
+
+// Like run, with the output and the messages about files that can not be read going to the given
+// writers instead of stdout and stderr, so a whole run can be checked in the tests
+fn run_with_output(config: Config, out: &mut dyn Write, messages: &mut dyn Write) -> Result<Outcome, MinigrepError> {
+    let start_time = Instant::now();
+
+    let patterns = read_patterns(&config)?;
+
+    let matcher = Matcher::new(
+        &patterns,
+        config.ignore_case,
+        config.only_match_words,
+        config.use_regex,
+    )?;
+
+    // Without any file paths standard input is searched, except with -r where the
+    // current directory is searched like grep does
+    let file_paths = if !config.file_paths.is_empty() {
+        config.file_paths.clone()
+    } else if config.recursive {
+        vec![String::from(".")]
+    } else {
+        vec![String::from("-")]
+    };
+
+    // Like grep, file names are only shown when more than one file can be searched
+    let show_file_names = file_paths.len() > 1 || config.recursive;
+
+    let inputs = file_paths.iter().flat_map(|file_path| -> Box<dyn Iterator<Item = _> + Send> {
+        if file_path == "-" {
+            Box::new(std::iter::once(Ok(PathBuf::from("-"))))
+        } else {
+            let walk = Walk::new(&[file_path], config.recursive)
+                .with_ignore_files(!config.no_ignore)
+                .with_hidden(config.hidden)
+                .with_filter(config.file_filter.clone());
+            Box::new(walk)
+        }
+    });
+
+    let mut totals = Totals::default();
+
+    let searched = if config.threads > 1 {
+        search_in_parallel(inputs, show_file_names, &matcher, &config, out, messages, &mut totals)
+    } else {
+        search_in_order(inputs, show_file_names, &matcher, &config, out, messages, &mut totals)
+    };
+
+    totals.stats.elapsed = start_time.elapsed();
+
+    // The JSON summary already has the stats, so they are not printed to stderr as well
+    let summarized = if config.json && !config.quiet {
+        writeln!(out, "{}", json::summary_event(&totals.stats))
+    } else {
+        if config.show_stats {
+            totals.stats.display();
+        }
+        Ok(())
+    };
+
+    // When whoever reads the output goes away, like `head` does, there is no one left to tell,
+    // so the search just ends there
+    match searched.and(summarized) {
+        Err(error) if error.kind() != io::ErrorKind::BrokenPipe => return Err(MinigrepError::Output(error)),
+        _ => {}
+    }
+
+    Ok(totals.outcome(&config))
+}
+
+// What the files searched so far add up to
+#[derive(Default)]
+struct Totals {
+    stats: Stats,
+    had_file_errors: bool,
+    // Set once any line is printed, so "--" can also be printed between the groups of different files
+    printed_any: bool,
+}
+
+impl Totals {
+    // A file that can not be read is reported (unless -s is used) and skipped, so the rest of the
+    // files are still searched. Only a failure to write the output ends the search.
+    fn add(
+        &mut self,
+        result: Result<Stats, MinigrepError>,
+        config: &Config,
+        messages: &mut dyn Write,
+    ) -> io::Result<()> {
+        match result {
+            Ok(stats) => self.stats.add(&stats),
+            Err(MinigrepError::Output(error)) => return Err(error),
+            Err(error) => {
+                self.had_file_errors = true;
+                if !config.no_messages {
+                    // Like eprintln, a message that can not be written is not worth ending the search for
+                    let _ = writeln!(messages, "minigrep: {error}");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // With -q the answer is known at the first match, so nothing else has to be read
+    fn is_done(&self, config: &Config) -> bool {
+        config.quiet && self.stats.lines_matched > 0
+    }
+
+    // Like grep, an error wins over a match, except with -q where only the match is asked about
+    fn outcome(&self, config: &Config) -> Outcome {
+        let matched = self.stats.lines_matched > 0;
+
+        if self.had_file_errors && !(config.quiet && matched) {
+            Outcome::FileErrors
+        } else if matched {
+            Outcome::Matched
+        } else {
+            Outcome::NotMatched
+        }
+    }
+}
+
+// Searches the files one after the other on this thread, printing the lines as they are found
+fn search_in_order(
+    inputs: impl Iterator<Item = Result<PathBuf, WalkError>>,
+    show_file_names: bool,
+    matcher: &Matcher,
+    config: &Config,
+    out: &mut dyn Write,
+    messages: &mut dyn Write,
+    totals: &mut Totals,
+) -> io::Result<()> {
+    for input in inputs {
+        let result = input
+            .map_err(MinigrepError::from)
+            .and_then(|path| search_input(&path, show_file_names, matcher, config, out, &mut totals.printed_any));
+        totals.add(result, config, messages)?;
+
+        if totals.is_done(config) {
+            break;
+        }
+    }
 
     Ok(())
 }
+
+// What a worker thread found in one file
+struct FileResult {
+    // Where the file comes in the walk
+    number: usize,
+    // Everything printed for the file, which is written out in one piece so files never mix
+    output: Vec<u8>,
+    // Set if lines were printed, which "--" may have to separate from the lines of an earlier file
+    printed: bool,
+    result: Result<Stats, MinigrepError>,
+}
+
+// Searches the files on a pool of config.threads worker threads. The walk runs on a thread of its own
+// and sends the files to the workers through a channel, and the workers send back what they found.
+// Only this thread writes the output, one whole file at a time: as soon as a file is done,
+// or with --sort=path once all the files walked before it are written.
+fn search_in_parallel(
+    inputs: impl Iterator<Item = Result<PathBuf, WalkError>> + Send,
+    show_file_names: bool,
+    matcher: &Matcher,
+    config: &Config,
+    out: &mut dyn Write,
+    messages: &mut dyn Write,
+    totals: &mut Totals,
+) -> io::Result<()> {
+    let (job_sender, job_receiver) = mpsc::channel::<(usize, Result<PathBuf, WalkError>)>();
+    // Every worker takes the next file from the same receiver
+    let job_receiver = Mutex::new(job_receiver);
+    let (result_sender, result_receiver) = mpsc::channel::<FileResult>();
+    // Set when the rest of the files are not needed any more, like after the first match with -q
+    let stop = AtomicBool::new(false);
+
+    // "--" between files only shows up in front of lines printed with context, like in search_lines
+    let separate_files = (config.before_context > 0 || config.after_context > 0) && !config.only_matching;
+
+    let stop = &stop;
+
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            for (number, input) in inputs.enumerate() {
+                if stop.load(Ordering::Relaxed) || job_sender.send((number, input)).is_err() {
+                    break;
+                }
+            }
+            // Dropping the sender here tells the workers that no more files are coming
+        });
+
+        for _ in 0..config.threads {
+            let result_sender = result_sender.clone();
+            let job_receiver = &job_receiver;
+            // A clone has its own lazy DFA cache, so the workers do not wait on each other for it
+            let matcher = matcher.clone();
+
+            scope.spawn(move || {
+                loop {
+                    let job = job_receiver.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).recv();
+                    let Ok((number, input)) = job else { break };
+                    if stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+
+                    // Each file starts on its own, whether "--" goes in front of it is decided when it is written
+                    let mut output = Vec::new();
+                    let mut printed = false;
+                    let result = input
+                        .map_err(MinigrepError::from)
+                        .and_then(|path| search_input(&path, show_file_names, &matcher, config, &mut output, &mut printed));
+
+                    let file_result = FileResult { number, output, printed, result };
+                    if result_sender.send(file_result).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        // Only the workers can send results now, so the loop below ends when all of them are done
+        drop(result_sender);
+
+        // With --sort=path, files that are done before the ones walked earlier wait here for their turn
+        let mut waiting: BTreeMap<usize, FileResult> = BTreeMap::new();
+        let mut next_number = 0;
+
+        let mut written = Ok(());
+        for file_result in result_receiver {
+            if config.sort_by_path {
+                waiting.insert(file_result.number, file_result);
+                while let Some(file_result) = waiting.remove(&next_number) {
+                    next_number += 1;
+                    written = written.and_then(|_| write_file_result(file_result, separate_files, config, out, messages, totals));
+                }
+            } else {
+                written = write_file_result(file_result, separate_files, config, out, messages, totals);
+            }
+
+            if written.is_err() || totals.is_done(config) {
+                // Leaving the loop drops the receiver, so the workers stop at their next file as well
+                stop.store(true, Ordering::Relaxed);
+                break;
+            }
+        }
+
+        written
+    })
+}
+
+fn write_file_result(
+    file_result: FileResult,
+    separate_files: bool,
+    config: &Config,
+    out: &mut dyn Write,
+    messages: &mut dyn Write,
+    totals: &mut Totals,
+) -> io::Result<()> {
+    if separate_files && file_result.printed && totals.printed_any {
+        display_separator(out)?;
+    }
+    out.write_all(&file_result.output)?;
+    totals.printed_any |= file_result.printed;
+
+    totals.add(file_result.result, config, messages)
+}
+
+// Searches standard input for "-" and the file at the path otherwise
+fn search_input(
+    path: &Path,
+    show_file_name: bool,
+    matcher: &Matcher,
+    config: &Config,
+    out: &mut dyn Write,
+    printed_any: &mut bool,
+) -> Result<Stats, MinigrepError> {
+    if path == Path::new("-") {
+        let stdin_path = Path::new(STDIN_NAME);
+        return search_reader(io::stdin().lock(), stdin_path, show_file_name, matcher, config, out, printed_any);
+    }
+
+    let file = File::open(path).map_err(|error| MinigrepError::Io {
+        path: PathBuf::from(path),
+        error,
+    })?;
+    search_file(file, path, show_file_name, matcher, config, out, printed_any)
+}
+
+fn search_file(
+    file: File,
+    path: &Path,
+    show_file_name: bool,
+    matcher: &Matcher,
+    config: &Config,
+    out: &mut dyn Write,
+    printed_any: &mut bool,
+) -> Result<Stats, MinigrepError> {
+    let is_large_file = file
+        .metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.len() >= MMAP_THRESHOLD);
+
+    // The checks that need no bytes come first, so a file that is read anyway is never mapped.
+    // A file that can not be mapped can still be read.
+    if is_large_file
+        && !config.inverted_match
+        && config.encoding.is_none_or(|encoding| encoding == Encoding::Utf8)
+        && let Ok(map) = Mmap::map(&file)
+        && sniff_bom(&map).is_none()
+        && !(config.search_zip && is_gzip(&map))
+        && !is_tar(&map)
+        && !is_binary(&map)
+        && !map.contains(&b'\r')
+    {
+        let mut lines = CandidateLines::new(&map, matcher, config.before_context, config.after_context);
+        return search_lines(&mut lines, path, show_file_name, matcher, config, out, printed_any, false)
+            .map_err(MinigrepError::Output);
+    }
+
+    search_reader(BufReader::new(file), path, show_file_name, matcher, config, out, printed_any)
+}
+
+// Searches the reader one line at a time and prints the matching lines as soon as they are found,
+// so only the current line is kept in memory, no matter how big the input is.
+// With -z a gzip input is decompressed on the way, the members of a tar archive are searched one
+// after the other, and an input that is not in UTF-8 is transcoded to UTF-8.
+fn search_reader<R: BufRead>(
+    mut reader: R,
+    path: &Path,
+    show_file_name: bool,
+    matcher: &Matcher,
+    config: &Config,
+    out: &mut dyn Write,
+    printed_any: &mut bool,
+) -> Result<Stats, MinigrepError> {
+    // The first block is read anyway, so looking at it before the lines are read costs nothing.
+    // Files that are not compressed are searched as they are, so -z works on a mix of them.
+    let leading_block = reader.fill_buf().map_err(|error| read_error(path, error))?;
+    if config.search_zip && is_gzip(leading_block) {
+        let decompressed = BufReader::new(GzipReader::new(reader));
+        return search_unpacked(decompressed, path, show_file_name, matcher, config, out, printed_any);
+    }
+
+    search_unpacked(reader, path, show_file_name, matcher, config, out, printed_any)
+}
+
+// A tar archive is recognized by its first header, so a .tar.gz is one too once it is decompressed
+fn search_unpacked<R: BufRead>(
+    mut reader: R,
+    path: &Path,
+    show_file_name: bool,
+    matcher: &Matcher,
+    config: &Config,
+    out: &mut dyn Write,
+    printed_any: &mut bool,
+) -> Result<Stats, MinigrepError> {
+    let leading_block = reader.fill_buf().map_err(|error| read_error(path, error))?;
+    if is_tar(leading_block) {
+        return search_archive(reader, path, matcher, config, out, printed_any);
+    }
+
+    search_text(reader, path, show_file_name, matcher, config, out, printed_any)
+}
+
+// Searches every regular file in a tar archive like a file of its own, named after the archive
+// and the path in it, like bundle.tar:var/log/app.log. The members are read straight from the
+// archive, nothing is extracted. Members are not unpacked any further, so a .gz in a tar is searched as it is.
+fn search_archive<R: BufRead>(
+    reader: R,
+    path: &Path,
+    matcher: &Matcher,
+    config: &Config,
+    out: &mut dyn Write,
+    printed_any: &mut bool,
+) -> Result<Stats, MinigrepError> {
+    let mut archive = TarArchive::new(reader);
+    let mut stats = Stats::new();
+
+    while let Some(entry) = archive.next_entry().map_err(|error| read_error(path, error))? {
+        if entry.kind != EntryKind::File {
+            continue;
+        }
+
+        let mut member_path = OsString::from(path);
+        member_path.push(":");
+        member_path.push(&entry.path);
+
+        // The name of the member is always shown, since it is the only way to tell where a line is from
+        let member_stats = search_text(
+            BufReader::new(archive.entry_data()),
+            Path::new(&member_path),
+            true,
+            matcher,
+            config,
+            out,
+            printed_any,
+        )?;
+        stats.add(&member_stats);
+
+        if config.quiet && stats.lines_matched > 0 {
+            break;
+        }
+    }
+
+    Ok(stats)
+}
+
+fn search_text<R: BufRead>(
+    mut reader: R,
+    path: &Path,
+    show_file_name: bool,
+    matcher: &Matcher,
+    config: &Config,
+    out: &mut dyn Write,
+    printed_any: &mut bool,
+) -> Result<Stats, MinigrepError> {
+    let leading_block = reader.fill_buf().map_err(|error| read_error(path, error))?;
+
+    // The encoding given with --encoding wins over the one a byte order mark is for.
+    // The mark itself is not part of the text, so it would only get in the way of a ^ on the first line.
+    let encoding = config.encoding.or_else(|| sniff_bom(leading_block));
+    if let Some(encoding) = encoding
+        && leading_block.starts_with(encoding.bom())
+    {
+        reader.consume(encoding.bom().len());
+    }
+
+    match encoding {
+        // The lines are read from the UTF-8 text, so byte offsets and the bytes read count in that text
+        Some(encoding) if encoding != Encoding::Utf8 => {
+            let decoded = BufReader::new(DecodingReader::new(reader, encoding));
+            search_decoded(decoded, path, show_file_name, matcher, config, out, printed_any)
+        }
+        _ => search_decoded(reader, path, show_file_name, matcher, config, out, printed_any),
+    }
+}
+
+fn search_decoded<R: BufRead>(
+    mut reader: R,
+    path: &Path,
+    show_file_name: bool,
+    matcher: &Matcher,
+    config: &Config,
+    out: &mut dyn Write,
+    printed_any: &mut bool,
+) -> Result<Stats, MinigrepError> {
+    // UTF-16 has NUL bytes all over, so a file is only taken to be binary once it is decoded
+    let leading_block = reader.fill_buf().map_err(|error| read_error(path, error))?;
+    let binary = is_binary(leading_block);
+
+    // A binary file is searched as if it were empty, so it is counted and listed like a file without a match
+    if binary && config.binary_files == BinaryFiles::WithoutMatch {
+        let mut no_lines = LineReader::new(io::empty());
+        return search_lines(&mut no_lines, path, show_file_name, matcher, config, out, printed_any, binary)
+            .map_err(MinigrepError::Output);
+    }
+
+    // Lines are read one at a time as the search asks for them
+    let mut line_reader = LineReader::new(reader);
+
+    let stats = search_lines(&mut line_reader, path, show_file_name, matcher, config, out, printed_any, binary)
+        .map_err(MinigrepError::Output)?;
+
+    match line_reader.take_error() {
+        Some(error) => Err(read_error(path, error)),
+        None => Ok(stats),
+    }
+}
+
+// Searches the lines and prints the result for the file as the lines come in
+#[allow(clippy::too_many_arguments)]
+fn search_lines<S: LineSource>(
+    lines: &mut S,
+    path: &Path,
+    show_file_name: bool,
+    matcher: &Matcher,
+    config: &Config,
+    out: &mut dyn Write,
+    printed_any: &mut bool,
+    binary: bool,
+) -> io::Result<Stats> {
+    let mut stats = Stats::new();
+    stats.files_scanned = 1;
+
+    let path_name = path.display().to_string();
+    let file_name = if show_file_name { Some(path_name.as_str()) } else { None };
+
+    // JSON always has the path, with the raw bytes so names that are not UTF-8 are kept as they are
+    let json_path = path.as_os_str().as_encoded_bytes();
+    // The begin event is only printed once the file has a line to show, and only then an end event
+    let mut json_began = false;
+
+    // With -c and --count-matches only the count is printed, once the whole file is searched
+    let only_count = config.count || config.count_matches;
+    // With -l and -L only the path is printed, once it is known if the file has a selected line
+    let list_files = config.files_with_matches || config.files_without_match;
+
+    let show_separators = config.before_context > 0 || config.after_context > 0;
+
+    // Like grep, the lines of a binary file are not printed, only that it matches
+    let hide_binary_lines = binary && config.binary_files == BinaryFiles::Binary && !config.json;
+
+    // A new file always starts a new group
+    let mut previous_index_in_file: Option<usize> = None;
+
+    let search = Search::new(
+        &mut *lines,
+        matcher,
+        config.inverted_match,
+        config.before_context,
+        config.after_context,
+    )
+    .with_max_count(config.max_count);
+
+    for line in search {
+        let is_selected = line.kind == LineKind::Match;
+
+        if is_selected {
+            stats.lines_matched += 1;
+
+            // An inverted match has nothing to count inside the line, so the line itself is counted
+            stats.matches += if config.inverted_match { 1 } else { line.matches.len() as u64 };
+        }
+
+        // -q, -l and -L only ask if the file has a selected line, so the first one ends the search
+        if config.quiet || list_files {
+            if is_selected {
+                break;
+            }
+            continue;
+        }
+
+        if only_count {
+            continue;
+        }
+
+        if hide_binary_lines {
+            if is_selected {
+                display_binary_match(out, &path_name)?;
+                break;
+            }
+            continue;
+        }
+
+        // With -o there is no context, since only the matched parts of the lines are printed.
+        // JSON already has the matched parts of every line.
+        if config.only_matching && !config.json {
+            line.display_only_matching(
+                out,
+                file_name,
+                config.show_line_numbers,
+                config.show_column,
+                config.show_byte_offset,
+            )?;
+            if !line.matches.is_empty() {
+                *printed_any = true;
+            }
+            continue;
+        }
+
+        if config.json {
+            if !json_began {
+                writeln!(out, "{}", json::begin_event(json_path))?;
+                json_began = true;
+            }
+            writeln!(out, "{}", json::line_event(json_path, &line))?;
+            continue;
+        }
+
+        let starts_new_group = match previous_index_in_file {
+            Some(previous) => line.index != previous + 1,
+            None => *printed_any,
+        };
+        if show_separators && starts_new_group {
+            display_separator(out)?;
+        }
+
+        line.display(out, file_name, config.show_line_numbers, config.show_byte_offset)?;
+
+        previous_index_in_file = Some(line.index);
+        *printed_any = true;
+    }
+
+    stats.bytes_read = lines.bytes_read();
+
+    if list_files && !config.quiet {
+        let has_selected_line = stats.lines_matched > 0;
+        if has_selected_line == config.files_with_matches {
+            display_path(out, &path_name, config.null)?;
+        }
+    } else if only_count && !config.quiet {
+        let count = if config.count_matches {
+            stats.matches
+        } else {
+            stats.lines_matched
+        };
+        display_count(out, file_name, count)?;
+    }
+
+    if json_began {
+        writeln!(out, "{}", json::end_event(json_path, &stats))?;
+    }
+
+    Ok(stats)
+}
+
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_BLOCK_SIZE)].contains(&0)
+}
+
+fn read_error(path: &Path, error: io::Error) -> MinigrepError {
+    MinigrepError::Io {
+        path: PathBuf::from(path),
+        error,
+    }
+}
+
+// Collects the patterns given with -e (or positionally) and the ones in the -f files
+fn read_patterns(config: &Config) -> Result<Vec<String>, MinigrepError> {
+    let mut patterns = config.patterns.clone();
+
+    for pattern_file in &config.pattern_files {
+        let text = fs::read_to_string(pattern_file).map_err(|error| MinigrepError::Io {
+            path: PathBuf::from(pattern_file),
+            error,
+        })?;
+
+        // Like in grep, a blank line is an empty pattern, which matches every line
+        patterns.extend(text.lines().map(String::from));
+    }
+
+    Ok(patterns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    // Creates an empty directory in the system temp dir that is unique to the test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("minigrep-runner-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // Runs minigrep with the arguments, and returns how it ended with what it printed and the messages
+    fn run_args(args: &[&str]) -> (Outcome, String, String) {
+        let args: Vec<String> = std::iter::once("minigrep").chain(args.iter().copied()).map(String::from).collect();
+        let config = Config::build(&args).unwrap();
+
+        let mut out = Vec::new();
+        let mut messages = Vec::new();
+        let outcome = run_with_output(config, &mut out, &mut messages).unwrap();
+
+        (outcome, String::from_utf8(out).unwrap(), String::from_utf8(messages).unwrap())
+    }
+
+    fn path_str(path: &Path) -> &str {
+        path.to_str().unwrap()
+    }
+
+    #[test]
+    fn run_returns_outcome_of_search() {
+        let dir = temp_dir("outcome");
+        let file = dir.join("a.txt");
+        fs::write(&file, "hay\nneedle\n").unwrap();
+
+        assert_eq!(Outcome::Matched, run_args(&["needle", path_str(&file)]).0);
+        assert_eq!(Outcome::NotMatched, run_args(&["pin", path_str(&file)]).0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn run_with_blank_line_in_pattern_file_matches_every_line() {
+        let dir = temp_dir("blank-pattern");
+        let (file, patterns) = (dir.join("a.txt"), dir.join("patterns.txt"));
+        fs::write(&file, "hay\nneedle\n").unwrap();
+        fs::write(&patterns, "needle\n\n").unwrap();
+
+        for args in [vec!["-c", "-f"], vec!["-c", "-E", "-f"], vec!["-c", "-i", "-f"]] {
+            let args: Vec<&str> = args.into_iter().chain([path_str(&patterns), path_str(&file)]).collect();
+            assert_eq!("2\n", run_args(&args).1, "{args:?}");
+        }
+
+        // Without the blank line, only the pattern itself is looked for
+        fs::write(&patterns, "needle\n").unwrap();
+        assert_eq!("1\n", run_args(&["-c", "-f", path_str(&patterns), path_str(&file)]).1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn run_reports_file_errors_over_matches_unless_quiet() {
+        let dir = temp_dir("errors");
+        let file = dir.join("a.txt");
+        let missing = dir.join("missing.txt");
+        fs::write(&file, "needle\n").unwrap();
+
+        let (outcome, out, messages) = run_args(&["needle", path_str(&file), path_str(&missing)]);
+        assert_eq!(Outcome::FileErrors, outcome);
+        assert!(out.contains("a.txt:"));
+        assert!(messages.starts_with("minigrep: ") && messages.contains("missing.txt"));
+
+        // Only the match is asked about with -q, so the error that comes after it does not matter
+        let (outcome, out, _) = run_args(&["-q", "needle", path_str(&missing), path_str(&file)]);
+        assert_eq!(Outcome::Matched, outcome);
+        assert_eq!("", out);
+
+        // Without a match there is nothing to win over the error
+        assert_eq!(Outcome::FileErrors, run_args(&["-q", "pin", path_str(&file), path_str(&missing)]).0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn run_with_quiet_stops_at_first_match() {
+        let dir = temp_dir("quiet");
+        let file = dir.join("a.txt");
+        fs::write(&file, "needle\n").unwrap();
+
+        // The missing file is never opened, so there is no message about it
+        let (outcome, out, messages) = run_args(&["-q", "needle", path_str(&file), path_str(&dir.join("missing.txt"))]);
+        assert_eq!((Outcome::Matched, ""), (outcome, out.as_str()));
+        assert_eq!("", messages);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn run_with_no_messages_hides_file_errors() {
+        let dir = temp_dir("no-messages");
+        let missing = dir.join("missing.txt");
+
+        let (outcome, _, messages) = run_args(&["-s", "needle", path_str(&missing)]);
+        assert_eq!((Outcome::FileErrors, ""), (outcome, messages.as_str()));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn run_lists_files_with_and_without_match() {
+        let dir = temp_dir("list");
+        let (with, without) = (dir.join("with.txt"), dir.join("without.txt"));
+        fs::write(&with, "needle\nneedle\n").unwrap();
+        fs::write(&without, "hay\n").unwrap();
+        let files = [path_str(&with), path_str(&without)];
+
+        let (_, out, _) = run_args(&["-l", "needle", files[0], files[1]]);
+        assert_eq!(format!("{}\n", with.display()), out);
+
+        let (_, out, _) = run_args(&["-L", "needle", files[0], files[1]]);
+        assert_eq!(format!("{}\n", without.display()), out);
+
+        let (_, out, _) = run_args(&["-l0", "needle", files[0], files[1]]);
+        assert_eq!(format!("{}\0", with.display()), out);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn run_reports_binary_match_once() {
+        let dir = temp_dir("binary");
+        let file = dir.join("data.bin");
+        fs::write(&file, "needle\0\nneedle again\n").unwrap();
+
+        let (outcome, out, _) = run_args(&["needle", path_str(&file)]);
+        assert_eq!(Outcome::Matched, outcome);
+        assert_eq!(format!("Binary file {} matches\n", file.display()), out);
+
+        // A NUL byte after the leading block goes unnoticed, like in grep
+        let late_nul = dir.join("late.txt");
+        fs::write(&late_nul, format!("{}\nneedle\n\0", "x".repeat(BINARY_BLOCK_SIZE))).unwrap();
+        let (_, out, _) = run_args(&["-n", "needle", path_str(&late_nul)]);
+        assert!(out.starts_with("2: "));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn run_with_binary_files_without_match_counts_file_as_not_matching() {
+        let dir = temp_dir("binary-without-match");
+        let file = dir.join("data.bin");
+        fs::write(&file, "needle\0\n").unwrap();
+
+        let (outcome, out, _) = run_args(&["-c", "--binary-files=without-match", "needle", path_str(&file)]);
+        assert_eq!((Outcome::NotMatched, "0\n"), (outcome, out.as_str()));
+
+        let (_, out, _) = run_args(&["-L", "--binary-files=without-match", "needle", path_str(&file)]);
+        assert_eq!(format!("{}\n", file.display()), out);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn run_with_binary_files_text_prints_lines() {
+        let dir = temp_dir("binary-text");
+        let file = dir.join("data.bin");
+        fs::write(&file, "a\0b\nneedle\n").unwrap();
+
+        let (outcome, out, _) = run_args(&["-n", "--binary-files=text", "needle", path_str(&file)]);
+        assert_eq!(Outcome::Matched, outcome);
+        assert!(out.starts_with("2: ") && out.contains("needle"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    // A directory of files that each have matches far enough apart to make several context groups
+    fn many_files_dir(name: &str) -> PathBuf {
+        let dir = temp_dir(name);
+        for file in 0..20 {
+            let text: String = (0..30)
+                .map(|line| if line % 7 == 0 { format!("needle {file} {line}\n") } else { format!("hay {line}\n") })
+                .collect();
+            fs::write(dir.join(format!("file{file:02}.txt")), text).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn run_on_threads_sorted_by_path_prints_like_one_thread() {
+        let dir = many_files_dir("sorted");
+
+        let (_, in_order, _) = run_args(&["-r", "-n", "-C1", "-j1", "needle", path_str(&dir)]);
+        let (_, sorted, _) = run_args(&["-r", "-n", "-C1", "-j8", "--sort=path", "needle", path_str(&dir)]);
+
+        assert_eq!(in_order, sorted);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn run_on_threads_keeps_output_of_each_file_together() {
+        let dir = many_files_dir("together");
+
+        let (_, out, _) = run_args(&["-r", "-n", "-C1", "-j8", "needle", path_str(&dir)]);
+
+        // The lines of a file, and the "--" between its groups, never have another file's lines in between
+        let mut files_done: Vec<&str> = Vec::new();
+        let mut current_file = "";
+        let mut previous_line = "";
+        for line in out.lines() {
+            if line == "--" {
+                assert!(!previous_line.is_empty() && previous_line != "--", "separator after {previous_line:?}");
+                previous_line = line;
+                continue;
+            }
+
+            let file = &line[path_str(&dir).len()..][..1 + "fileNN.txt".len()];
+            if file != current_file {
+                assert!(!files_done.contains(&file), "{file} is split up");
+                // Every file after the first starts a new group, so "--" comes in between
+                if !current_file.is_empty() {
+                    assert_eq!("--", previous_line);
+                }
+                files_done.push(current_file);
+                current_file = file;
+            }
+            previous_line = line;
+        }
+        files_done.push(current_file);
+
+        assert_eq!(21, files_done.len()); // The 20 files and the "" it started with
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn run_counts_byte_offsets_in_transcoded_text() {
+        let dir = temp_dir("transcoded");
+        let file = dir.join("utf16.txt");
+        let text: Vec<u8> = "ab\nneedle\n".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        fs::write(&file, [b"\xFF\xFE".as_slice(), &text].concat()).unwrap();
+
+        // The second line is at byte 8 of the file, after the mark and "ab\n" in UTF-16
+        let (_, out, _) = run_args(&["-n", "-b", "needle", path_str(&file)]);
+        assert!(out.starts_with("2:3: "), "{out:?}");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn is_binary_only_looks_at_leading_block() {
+        let mut bytes = vec![b'a'; BINARY_BLOCK_SIZE + 1];
+        assert!(!is_binary(&bytes));
+
+        bytes[BINARY_BLOCK_SIZE] = 0;
+        assert!(!is_binary(&bytes));
+
+        bytes[BINARY_BLOCK_SIZE - 1] = 0;
+        assert!(is_binary(&bytes));
+    }
+
+    #[test]
+    fn totals_outcome_lets_error_win_except_with_quiet() {
+        let config = Config::build(&[String::from("minigrep"), String::from("needle")]).unwrap();
+        let quiet = Config::build(&[String::from("minigrep"), String::from("-q"), String::from("needle")]).unwrap();
+
+        let mut totals = Totals::default();
+        assert_eq!(Outcome::NotMatched, totals.outcome(&config));
+
+        totals.stats.lines_matched = 1;
+        assert_eq!(Outcome::Matched, totals.outcome(&config));
+
+        totals.had_file_errors = true;
+        assert_eq!(Outcome::FileErrors, totals.outcome(&config));
+        assert_eq!(Outcome::Matched, totals.outcome(&quiet));
+
+        totals.stats.lines_matched = 0;
+        assert_eq!(Outcome::FileErrors, totals.outcome(&quiet));
+    }
+
+    #[test]
+    fn outcome_exit_codes_follow_grep() {
+        assert_eq!(
+            (0, 1, 2),
+            (Outcome::Matched.exit_code(), Outcome::NotMatched.exit_code(), Outcome::FileErrors.exit_code())
+        );
+    }
+}
@@ -1,82 +1,218 @@
-use crate::model::{Content, Line};
+use std::collections::VecDeque;
+
+use crate::matcher::Matcher;
+use crate::model::{Content, Line, LineKind, Match};
+use crate::regex::char_count;
 
 impl Content {
+    // Keeps the selected lines, plus up to before_context lines before and after_context lines after
+    // each of them as context lines
     pub fn search(
         self,
-        query: &str,
-        ignore_case: bool,
-        only_match_words: bool,
+        matcher: &Matcher,
         inverted_match: bool,
+        before_context: usize,
+        after_context: usize,
     ) -> Self {
-        let mut searched_content = Self::new();
-
-        for line in self.lines {
-            let maybe_searched_line = line.search(
-                query,
-                ignore_case,
-                only_match_words,
-                inverted_match,
-            );
-
-            match maybe_searched_line {
-                Some(searched_line) => searched_content.lines.push(searched_line),
-                None => {}
-            }
-        }
+        let search = Search::new(self.lines.into_iter(), matcher, inverted_match, before_context, after_context);
 
-        searched_content
+        Self::from_lines(search.collect())
     }
 }
 
 impl Line {
-    pub fn search(
-        self,
-        query: &str,
-        ignore_case: bool,
-        only_match_words: bool,
-        inverted_match: bool,
-    ) -> Option<Self> {
-        // Make text case aware
-        let case_aware_text = if ignore_case {
-            &self.text.to_lowercase()
+    pub fn search(mut self, matcher: &Matcher, inverted_match: bool) -> Option<Self> {
+        if self.select(matcher, inverted_match) {
+            Some(self)
         } else {
-            &self.text
-        };
+            None
+        }
+    }
 
-        let case_aware_query = if ignore_case {
-            &query.to_lowercase()
-        } else {
-            query
-        };
+    // A line is selected if it matches, or if it does not match when the match is inverted
+    pub fn is_selected(&self, matcher: &Matcher, inverted_match: bool) -> bool {
+        let line_matches = matcher.is_match(&self.text);
+
+        if inverted_match { !line_matches } else { line_matches }
+    }
 
-        let mut line_matches = false;
+    // Like is_selected, but also records where the matches are in a selected line.
+    // An inverted match selects the lines without any matches, so there is nothing to record then.
+    pub fn select(&mut self, matcher: &Matcher, inverted_match: bool) -> bool {
+        let is_selected = self.is_selected(matcher, inverted_match);
 
-        if only_match_words {
-            let words: Vec<String> = case_aware_text
-                .split_whitespace()
-                .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
-                .filter(|word| !word.is_empty())
-                .map(|s| String::from(s))
-                .collect();
-            for word in words {
-                if query == word {
-                    line_matches = true;
-                    break;
+        if is_selected && !inverted_match {
+            self.matches = self.find_matches(matcher);
+        }
+
+        is_selected
+    }
+
+    // Returns every match in the line, in order
+    pub fn find_matches(&self, matcher: &Matcher) -> Vec<Match> {
+        let mut matches = Vec::new();
+
+        // Chars are counted on from the end of the previous match, so the text is only walked once
+        let mut byte_offset = 0;
+        let mut char_offset = 0;
+
+        for (match_start, match_end) in matcher.find_iter(&self.text) {
+            let char_start = char_offset + char_count(&self.text[byte_offset..match_start]);
+            let char_end = char_start + char_count(&self.text[match_start..match_end]);
+
+            matches.push(Match::new(self.index, match_start..match_end, char_start..char_end));
+
+            byte_offset = match_end;
+            char_offset = char_end;
+        }
+
+        matches
+    }
+}
+
+// Searches the lines one at a time as they are pulled from it, and yields the selected lines
+// and their context lines. Nothing is searched ahead, so the caller can stop at any point, and
+// the lines never have to be in memory all at once.
+pub struct Search<'m, I> {
+    lines: I,
+    matcher: &'m Matcher,
+    inverted_match: bool,
+    context_window: ContextWindow,
+    // Lines that are ready to be yielded, since a single line can make earlier lines context
+    ready_lines: VecDeque<Line>,
+    // With a max count (-m) the search ends once that many lines are selected
+    max_count: Option<usize>,
+    selected_count: usize,
+    after_context: usize,
+    // How many more lines to show after the last selected line, once the max count is reached
+    trailing_context_left: usize,
+}
+
+impl<'m, I: Iterator<Item = Line>> Search<'m, I> {
+    pub fn new(
+        lines: I,
+        matcher: &'m Matcher,
+        inverted_match: bool,
+        before_context: usize,
+        after_context: usize,
+    ) -> Self {
+        Self {
+            lines,
+            matcher,
+            inverted_match,
+            context_window: ContextWindow::new(before_context, after_context),
+            ready_lines: VecDeque::new(),
+            max_count: None,
+            selected_count: 0,
+            after_context,
+            trailing_context_left: 0,
+        }
+    }
+
+    pub fn with_max_count(mut self, max_count: Option<usize>) -> Self {
+        self.max_count = max_count;
+        self
+    }
+
+    fn is_max_count_reached(&self) -> bool {
+        self.max_count.is_some_and(|max_count| self.selected_count >= max_count)
+    }
+}
+
+impl<I: Iterator<Item = Line>> Iterator for Search<'_, I> {
+    type Item = Line;
+
+    fn next(&mut self) -> Option<Line> {
+        loop {
+            if let Some(line) = self.ready_lines.pop_front() {
+                return Some(line);
+            }
+
+            // Once enough lines are selected, only the context after the last of them is left to show.
+            // Like in grep, those lines are shown as context even if they would have been selected.
+            if self.is_max_count_reached() {
+                if self.trailing_context_left == 0 {
+                    return None;
                 }
+                self.trailing_context_left -= 1;
+
+                let line = self.lines.next()?;
+                return Some(Line {
+                    kind: LineKind::Context,
+                    ..line
+                });
             }
-        } else {
-            if case_aware_text.contains(case_aware_query) {
-                line_matches = true;
-            };
-        };
 
-        let line: Option<Line> = if inverted_match {
-            if !line_matches { Some(self) } else { None }
-        } else {
-            if line_matches { Some(self) } else { None }
-        };
+            let mut line = self.lines.next()?;
+            let is_selected = line.select(self.matcher, self.inverted_match);
+            if is_selected {
+                self.selected_count += 1;
+                self.trailing_context_left = self.after_context;
+            }
+
+            self.ready_lines.extend(self.context_window.push(line, is_selected));
+        }
+    }
+}
+
+// Decides which lines to show as context while the lines are fed to it one at a time, so it works
+// the same on a whole file in memory and on an endless stream.
+// Since every line is returned at most once, context windows that overlap are merged on their own.
+pub struct ContextWindow {
+    before_context: usize,
+    after_context: usize,
+    // The latest lines that were not shown, kept in case the next line is selected
+    before_lines: VecDeque<Line>,
+    // How many more lines to show after the last selected line
+    after_lines_left: usize,
+}
+
+impl ContextWindow {
+    pub fn new(before_context: usize, after_context: usize) -> Self {
+        Self {
+            before_context,
+            after_context,
+            before_lines: VecDeque::with_capacity(before_context),
+            after_lines_left: 0,
+        }
+    }
+
+    // Returns the lines to show, in order, now that this line is known
+    pub fn push(&mut self, line: Line, is_selected: bool) -> Vec<Line> {
+        if is_selected {
+            let mut lines: Vec<Line> = self
+                .before_lines
+                .drain(..)
+                .map(|line| Line {
+                    kind: LineKind::Context,
+                    ..line
+                })
+                .collect();
+            lines.push(Line {
+                kind: LineKind::Match,
+                ..line
+            });
+
+            self.after_lines_left = self.after_context;
+            return lines;
+        }
+
+        if self.after_lines_left > 0 {
+            self.after_lines_left -= 1;
+            return vec![Line {
+                kind: LineKind::Context,
+                ..line
+            }];
+        }
+
+        if self.before_context > 0 {
+            if self.before_lines.len() == self.before_context {
+                self.before_lines.pop_front();
+            }
+            self.before_lines.push_back(line);
+        }
 
-        line
+        Vec::new()
     }
 }
 
@@ -84,20 +220,31 @@ impl Line {
 mod test {
     use super::*;
 
+    fn matcher(query: &str, ignore_case: bool, only_match_words: bool) -> Matcher {
+        Matcher::new(&[query], ignore_case, only_match_words, false).unwrap()
+    }
+
+    // Adds the expected match to a line with only ASCII text, where bytes and chars are the same
+    fn with_match(line: Line, start: usize, end: usize) -> Line {
+        let matches = vec![Match::new(line.index, start..end, start..end)];
+
+        Line { matches, ..line }
+    }
+
     #[test]
     fn line_search_returns_line_if_containing_query() {
         let line = Line::new(1, String::from("This minigrep tool is amazing!"));
 
-        let maybe_searched_line = line.clone().search("minigrep", false, false, false);
+        let maybe_searched_line = line.clone().search(&matcher("minigrep", false, false), false);
 
-        assert_eq!(Some(line), maybe_searched_line)
+        assert_eq!(Some(with_match(line, 5, 13)), maybe_searched_line)
     }
 
     #[test]
     fn line_search_does_not_return_line_if_not_containing_query() {
         let line = Line::new(1, String::from("This minigrep tool is amazing!"));
 
-        let maybe_searched_line = line.search("hello", false, false, false);
+        let maybe_searched_line = line.search(&matcher("hello", false, false), false);
 
         assert_eq!(None, maybe_searched_line)
     }
@@ -106,16 +253,16 @@ mod test {
     fn line_search_ignore_case_when_enabled() {
         let line = Line::new(1, String::from("Me too"));
 
-        let maybe_searched_line = line.clone().search("TO", true, false, false);
+        let maybe_searched_line = line.clone().search(&matcher("TO", true, false), false);
 
-        assert_eq!(Some(line), maybe_searched_line)
+        assert_eq!(Some(with_match(line, 3, 5)), maybe_searched_line)
     }
 
     #[test]
     fn line_search_does_not_ignore_case_when_disabled() {
         let line = Line::new(1, String::from("Me too"));
 
-        let maybe_searched_line = line.search("TO", false, false, false);
+        let maybe_searched_line = line.search(&matcher("TO", false, false), false);
 
         assert_eq!(None, maybe_searched_line)
     }
@@ -124,7 +271,7 @@ mod test {
     fn line_search_only_matches_words_when_enabled() {
         let line = Line::new(1, String::from("Me too"));
 
-        let maybe_searched_line = line.search("to", false, true, false);
+        let maybe_searched_line = line.search(&matcher("to", false, true), false);
 
         assert_eq!(None, maybe_searched_line)
     }
@@ -133,16 +280,16 @@ mod test {
     fn line_search_does_not_only_match_words_when_disabled() {
         let line = Line::new(1, String::from("Me too"));
 
-        let maybe_searched_line = line.clone().search("to", false, false, false);
+        let maybe_searched_line = line.clone().search(&matcher("to", false, false), false);
 
-        assert_eq!(Some(line), maybe_searched_line)
+        assert_eq!(Some(with_match(line, 3, 5)), maybe_searched_line)
     }
 
     #[test]
     fn line_search_inverts_match_when_enabled() {
         let line = Line::new(1, String::from("Me too"));
 
-        let maybe_searched_line = line.search("to", false, false, true);
+        let maybe_searched_line = line.search(&matcher("to", false, false), true);
 
         assert_eq!(None, maybe_searched_line)
     }
@@ -151,9 +298,9 @@ mod test {
     fn line_search_does_not_invert_match_when_disabled() {
         let line = Line::new(1, String::from("Me too"));
 
-        let maybe_searched_line = line.clone().search("to", false, false, false);
+        let maybe_searched_line = line.clone().search(&matcher("to", false, false), false);
 
-        assert_eq!(Some(line), maybe_searched_line)
+        assert_eq!(Some(with_match(line, 3, 5)), maybe_searched_line)
     }
 
 
@@ -161,8 +308,187 @@ mod test {
     fn line_search_ignore_case_only_match_words_inverted_match_work_togheter() {
         let line = Line::new(1, String::from("Then there's a pair of us - don't tell!"));
 
-        let maybe_searched_line = line.clone().search("AI", true, true, true);
+        let maybe_searched_line = line.clone().search(&matcher("AI", true, true), true);
 
         assert_eq!(Some(line), maybe_searched_line)
     }
+
+    #[test]
+    fn line_search_only_matches_words_case_insensitively() {
+        let line = Line::new(1, String::from("Then there's a pair of us - don't tell!"));
+
+        let maybe_searched_line = line.clone().search(&matcher("THEN", true, true), false);
+
+        assert_eq!(Some(with_match(line, 0, 4)), maybe_searched_line)
+    }
+
+    #[test]
+    fn line_search_uses_regex_when_enabled() {
+        let line = Line::new(1, String::from("fatal error 42 in worker"));
+        let matcher = Matcher::new(&[r"err(or)?\s+\d+"], false, false, true).unwrap();
+
+        let maybe_searched_line = line.clone().search(&matcher, false);
+
+        assert_eq!(Some(with_match(line, 6, 14)), maybe_searched_line)
+    }
+
+    fn lines(texts: &[&str]) -> Vec<Line> {
+        texts
+            .iter()
+            .enumerate()
+            .map(|(index, text)| Line::new(index + 1, String::from(*text)))
+            .collect()
+    }
+
+    #[test]
+    fn content_search_without_context_keeps_only_selected_lines() {
+        let content = Content::from_lines(lines(&["a", "match", "b", "c"]));
+
+        let searched_content = content.search(&matcher("match", false, false), false, 0, 0);
+
+        assert_eq!(
+            Content::from_lines(vec![with_match(Line::new(2, String::from("match")), 0, 5)]),
+            searched_content
+        )
+    }
+
+    #[test]
+    fn content_search_keeps_context_lines_around_matches() {
+        let content = Content::from_lines(lines(&["a", "b", "match", "c", "d", "e"]));
+
+        let searched_content = content.search(&matcher("match", false, false), false, 1, 2);
+
+        assert_eq!(
+            Content::from_lines(vec![
+                Line::context(2, String::from("b")),
+                with_match(Line::new(3, String::from("match")), 0, 5),
+                Line::context(4, String::from("c")),
+                Line::context(5, String::from("d")),
+            ]),
+            searched_content
+        )
+    }
+
+    #[test]
+    fn content_search_merges_overlapping_context() {
+        let content = Content::from_lines(lines(&["match", "a", "match", "b", "c", "d", "match"]));
+
+        let searched_content = content.search(&matcher("match", false, false), false, 1, 1);
+
+        assert_eq!(
+            Content::from_lines(vec![
+                with_match(Line::new(1, String::from("match")), 0, 5),
+                Line::context(2, String::from("a")),
+                with_match(Line::new(3, String::from("match")), 0, 5),
+                Line::context(4, String::from("b")),
+                Line::context(6, String::from("d")),
+                with_match(Line::new(7, String::from("match")), 0, 5),
+            ]),
+            searched_content
+        )
+    }
+
+    #[test]
+    fn content_search_context_follows_inverted_match() {
+        let content = Content::from_lines(lines(&["match", "a", "match"]));
+
+        let searched_content = content.search(&matcher("match", false, false), true, 1, 0);
+
+        assert_eq!(
+            Content::from_lines(vec![
+                Line::context(1, String::from("match")),
+                Line::new(2, String::from("a")),
+            ]),
+            searched_content
+        )
+    }
+
+    #[test]
+    fn line_find_matches_returns_byte_and_char_ranges() {
+        let line = Line::new(4, String::from("Hej på dig, på"));
+
+        let matches = line.find_matches(&matcher("på", false, false));
+
+        assert_eq!(
+            vec![Match::new(4, 4..7, 4..6), Match::new(4, 13..16, 12..14)],
+            matches
+        )
+    }
+
+    #[test]
+    fn line_select_does_not_record_matches_when_inverted() {
+        let mut line = Line::new(1, String::from("Me too"));
+
+        let is_selected = line.select(&matcher("monkey", false, false), true);
+
+        assert!(is_selected);
+        assert!(line.matches.is_empty());
+    }
+
+    #[test]
+    fn search_stops_after_max_count_selected_lines() {
+        let matcher = matcher("match", false, false);
+        let lines = lines(&["match", "a", "match", "match"]);
+
+        let searched_lines: Vec<Line> = Search::new(lines.into_iter(), &matcher, false, 0, 0)
+            .with_max_count(Some(2))
+            .collect();
+
+        assert_eq!(
+            vec![
+                with_match(Line::new(1, String::from("match")), 0, 5),
+                with_match(Line::new(3, String::from("match")), 0, 5),
+            ],
+            searched_lines
+        )
+    }
+
+    #[test]
+    fn search_shows_trailing_context_after_max_count() {
+        let matcher = matcher("match", false, false);
+        let lines = lines(&["a", "match", "match", "b", "c"]);
+
+        let searched_lines: Vec<Line> = Search::new(lines.into_iter(), &matcher, false, 1, 2)
+            .with_max_count(Some(1))
+            .collect();
+
+        assert_eq!(
+            vec![
+                Line::context(1, String::from("a")),
+                with_match(Line::new(2, String::from("match")), 0, 5),
+                Line::context(3, String::from("match")),
+                Line::context(4, String::from("b")),
+            ],
+            searched_lines
+        )
+    }
+
+    #[test]
+    fn search_max_count_counts_inverted_matches() {
+        let matcher = matcher("match", false, false);
+        let lines = lines(&["match", "a", "b", "c"]);
+
+        let searched_lines: Vec<Line> = Search::new(lines.into_iter(), &matcher, true, 0, 0)
+            .with_max_count(Some(2))
+            .collect();
+
+        assert_eq!(
+            vec![Line::new(2, String::from("a")), Line::new(3, String::from("b"))],
+            searched_lines
+        )
+    }
+
+    #[test]
+    fn search_pulls_no_lines_after_max_count() {
+        let matcher = matcher("match", false, false);
+        let mut pulled = 0;
+        let lines = lines(&["match", "a", "b"]).into_iter().inspect(|_| pulled += 1);
+
+        let searched_lines: Vec<Line> = Search::new(lines, &matcher, false, 0, 0)
+            .with_max_count(Some(1))
+            .collect();
+
+        assert_eq!(1, searched_lines.len());
+        assert_eq!(1, pulled);
+    }
 }
@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+// Counts what a search went through, per file, and summed up over all files for --stats
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Stats {
+    pub files_scanned: u64,
+    pub bytes_read: u64,
+    pub lines_matched: u64,
+    // The number of individual matches, which can be more than one per line
+    pub matches: u64,
+    // Wall clock time of the whole run, which is only set on the summed up stats
+    pub elapsed: Duration,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, other: &Stats) {
+        self.files_scanned += other.files_scanned;
+        self.bytes_read += other.bytes_read;
+        self.lines_matched += other.lines_matched;
+        self.matches += other.matches;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_add_sums_counts() {
+        let mut stats = Stats {
+            files_scanned: 1,
+            bytes_read: 100,
+            lines_matched: 3,
+            matches: 4,
+            elapsed: Duration::from_millis(5),
+        };
+        let other = Stats {
+            files_scanned: 2,
+            bytes_read: 50,
+            lines_matched: 1,
+            matches: 1,
+            elapsed: Duration::from_millis(10),
+        };
+
+        stats.add(&other);
+
+        assert_eq!(
+            Stats {
+                files_scanned: 3,
+                bytes_read: 150,
+                lines_matched: 4,
+                matches: 5,
+                elapsed: Duration::from_millis(5),
+            },
+            stats
+        )
+    }
+}
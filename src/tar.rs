@@ -0,0 +1,445 @@
+use std::io::{self, Read};
+
+// Goes through the members of a tar archive as it is read, so the files in it can be searched
+// without extracting them to disk. A tar archive is a series of 512 byte blocks: every member is
+// a header block followed by its data, padded to a whole block, and the archive ends with blocks of zeros.
+//
+// Names of up to 100 bytes fit in the header itself, and the ustar format adds a prefix of up to
+// 155 bytes. Longer names come before the member they are for, in a GNU long name member ('L')
+// or in a pax extended header ('x') with a path record.
+pub struct TarArchive<R> {
+    reader: R,
+    // What is left of the data of the current member, and of the padding after it
+    data_left: u64,
+    padding_left: u64,
+    finished: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TarEntry {
+    pub path: String,
+    pub kind: EntryKind,
+    pub size: u64,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EntryKind {
+    File,
+    Directory,
+    // Links, devices and the like, which have no text of their own to search
+    Other,
+}
+
+const BLOCK_SIZE: u64 = 512;
+
+// Where the fields are in a header block
+const NAME: std::ops::Range<usize> = 0..100;
+const SIZE: std::ops::Range<usize> = 124..136;
+const CHECKSUM: std::ops::Range<usize> = 148..156;
+const TYPE_FLAG: usize = 156;
+const MAGIC: std::ops::Range<usize> = 257..263;
+const PREFIX: std::ops::Range<usize> = 345..500;
+
+// Names longer than this are not believed, so a broken archive can not ask for any amount of memory
+const MAX_LONG_NAME: u64 = 64 * 1024;
+// The length and the key of a pax record are never longer than this in a sound archive
+const MAX_PAX_FIELD: usize = 256;
+
+// Checks if a block looks like a tar header, with the ustar magic (which GNU tar writes too) and a
+// checksum that adds up, so an archive can be recognized by its first block
+pub fn is_tar(bytes: &[u8]) -> bool {
+    let Some(header) = bytes.get(..BLOCK_SIZE as usize) else {
+        return false;
+    };
+
+    header[MAGIC].starts_with(b"ustar") && checksum_matches(header)
+}
+
+impl<R: Read> TarArchive<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            data_left: 0,
+            padding_left: 0,
+            finished: false,
+        }
+    }
+
+    // Moves on to the next member, skipping what is left of the current one. None is the end of the archive.
+    pub fn next_entry(&mut self) -> io::Result<Option<TarEntry>> {
+        let mut long_path: Option<String> = None;
+
+        loop {
+            self.skip_rest_of_entry()?;
+            if self.finished {
+                return Ok(None);
+            }
+
+            let mut header = [0; BLOCK_SIZE as usize];
+            if !self.read_block(&mut header)? || header.iter().all(|&byte| byte == 0) {
+                self.finished = true;
+                return Ok(None);
+            }
+            if !checksum_matches(&header) {
+                return Err(invalid_tar("header checksum does not match"));
+            }
+
+            let size = parse_size(&header[SIZE])?;
+            self.data_left = size;
+            self.padding_left = (BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE;
+
+            let kind = match header[TYPE_FLAG] {
+                b'0' | b'\0' | b'7' => EntryKind::File,
+                b'5' => EntryKind::Directory,
+                b'L' => {
+                    let name = self.read_data_string(size)?;
+                    long_path = Some(String::from(name.trim_end_matches('\0')));
+                    continue;
+                }
+                b'x' => {
+                    if let Some(path) = self.read_pax_path()? {
+                        long_path = Some(path);
+                    }
+                    continue;
+                }
+                // GNU long link names ('K') and pax global headers ('g') say nothing about the name of a member
+                b'K' | b'g' => continue,
+                _ => EntryKind::Other,
+            };
+
+            let path = match long_path.take() {
+                Some(path) => path,
+                None => header_path(&header),
+            };
+            return Ok(Some(TarEntry { path, kind, size }));
+        }
+    }
+
+    // The data of the current member. It can be read as far as needed, the rest is skipped by next_entry.
+    pub fn entry_data(&mut self) -> EntryData<'_, R> {
+        EntryData { archive: self }
+    }
+
+    // Reads a whole block, or returns false if the input ends right before it
+    fn read_block(&mut self, block: &mut [u8]) -> io::Result<bool> {
+        let mut filled = 0;
+        while filled < block.len() {
+            match self.reader.read(&mut block[filled..])? {
+                0 if filled == 0 => return Ok(false),
+                0 => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "tar archive ends in a header")),
+                read => filled += read,
+            }
+        }
+        Ok(true)
+    }
+
+    fn read_data_string(&mut self, size: u64) -> io::Result<String> {
+        if size > MAX_LONG_NAME {
+            return Err(invalid_tar("long name is too long"));
+        }
+
+        let mut data = Vec::new();
+        self.entry_data().read_to_end(&mut data)?;
+        if (data.len() as u64) < size {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "tar archive ends in a member"));
+        }
+        Ok(String::from_utf8_lossy(&data).into_owned())
+    }
+
+    // Pax records look like "30 path=some/long/file/name\n", where the number is the length of the
+    // whole record. A pax header can also hold big records that have nothing to do with the name,
+    // like extended attributes and ACLs, so the records are read one at a time and only a path is
+    // kept. Records that do not parse end the search for a path, but not the archive.
+    fn read_pax_path(&mut self) -> io::Result<Option<String>> {
+        let mut data = self.entry_data();
+
+        while let Some(length) = read_field(&mut data, b' ')? {
+            let Some(record_length) = std::str::from_utf8(&length).ok().and_then(|length| length.parse::<u64>().ok())
+            else {
+                break;
+            };
+            let Some(key) = read_field(&mut data, b'=')? else {
+                break;
+            };
+
+            // What is left of the record is the value and the newline after it
+            let field_lengths = (length.len() + key.len() + 2) as u64;
+            let Some(value_length) = record_length.checked_sub(field_lengths) else {
+                break;
+            };
+            let mut value = (&mut data).take(value_length);
+
+            if key == b"path" && value_length <= MAX_LONG_NAME {
+                let mut path = Vec::new();
+                value.read_to_end(&mut path)?;
+                let path = String::from_utf8_lossy(&path);
+                return Ok(Some(String::from(path.trim_end_matches('\n'))));
+            }
+            io::copy(&mut value, &mut io::sink())?;
+        }
+
+        Ok(None)
+    }
+
+    fn skip_rest_of_entry(&mut self) -> io::Result<()> {
+        let left = self.data_left + self.padding_left;
+        if left == 0 {
+            return Ok(());
+        }
+
+        let skipped = io::copy(&mut (&mut self.reader).take(left), &mut io::sink())?;
+        if skipped < left {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "tar archive ends in a member"));
+        }
+        self.data_left = 0;
+        self.padding_left = 0;
+        Ok(())
+    }
+}
+
+// Reads the data of one member, and ends where the member ends
+pub struct EntryData<'a, R> {
+    archive: &'a mut TarArchive<R>,
+}
+
+impl<R: Read> Read for EntryData<'_, R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let wanted = buffer.len().min(usize::try_from(self.archive.data_left).unwrap_or(usize::MAX));
+        if wanted == 0 {
+            return Ok(0);
+        }
+
+        let read = self.archive.reader.read(&mut buffer[..wanted])?;
+        if read == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "tar archive ends in a member"));
+        }
+        self.archive.data_left -= read as u64;
+        Ok(read)
+    }
+}
+
+fn invalid_tar(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid tar archive: {message}"))
+}
+
+// The checksum is the sum of all bytes of the header, with the checksum field itself taken as spaces
+fn checksum_matches(header: &[u8]) -> bool {
+    let sum: u64 = header
+        .iter()
+        .enumerate()
+        .map(|(index, &byte)| if CHECKSUM.contains(&index) { u64::from(b' ') } else { u64::from(byte) })
+        .sum();
+
+    parse_octal(&header[CHECKSUM]) == Some(sum)
+}
+
+// Numbers are octal text, ended by a NUL or a space. GNU tar writes sizes that do not fit
+// (8 GiB and more) as a big endian binary number instead, marked by the highest bit of the first byte.
+fn parse_size(field: &[u8]) -> io::Result<u64> {
+    if field[0] & 0x80 != 0 {
+        let mut size: u64 = u64::from(field[0] & 0x7F);
+        for &byte in &field[1..] {
+            size = size
+                .checked_mul(256)
+                .map(|size| size + u64::from(byte))
+                .ok_or_else(|| invalid_tar("member size is too big"))?;
+        }
+        return Ok(size);
+    }
+
+    parse_octal(field).ok_or_else(|| invalid_tar("member size is not a number"))
+}
+
+fn parse_octal(field: &[u8]) -> Option<u64> {
+    let digits = field
+        .iter()
+        .skip_while(|&&byte| byte == b' ')
+        .take_while(|&&byte| byte != 0 && byte != b' ');
+
+    let mut value: u64 = 0;
+    for &digit in digits {
+        if !(b'0'..=b'7').contains(&digit) {
+            return None;
+        }
+        value = value.checked_mul(8)? + u64::from(digit - b'0');
+    }
+    Some(value)
+}
+
+// A NUL terminated field, or the whole field when it is full
+fn field_text(field: &[u8]) -> String {
+    let end = field.iter().position(|&byte| byte == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+fn header_path(header: &[u8]) -> String {
+    let name = field_text(&header[NAME]);
+
+    // Only POSIX ustar has the prefix there, the old GNU format keeps other fields in that place
+    let prefix = if &header[MAGIC] == b"ustar\0" {
+        field_text(&header[PREFIX])
+    } else {
+        String::new()
+    };
+
+    if prefix.is_empty() { name } else { format!("{prefix}/{name}") }
+}
+
+// Reads a field up to the delimiter, which is not part of it. None is the end of the data, or a field
+// longer than MAX_PAX_FIELD, which can only be a broken record.
+fn read_field(reader: &mut impl Read, delimiter: u8) -> io::Result<Option<Vec<u8>>> {
+    let mut field = Vec::new();
+    let mut byte = [0];
+
+    while field.len() <= MAX_PAX_FIELD {
+        if reader.read(&mut byte)? == 0 {
+            return Ok(None);
+        }
+        if byte[0] == delimiter {
+            return Ok(Some(field));
+        }
+        field.push(byte[0]);
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Members: var/log/app.log, a directory, a file with a name longer than 100 bytes, a symlink and
+    // a file in deep/deep/... Both long names are GNU long name members. Made with Python's tarfile.
+    const GNU_ARCHIVE: &[u8] = include_bytes!("../fixtures/bundle.tar");
+    // The same members in POSIX format, where the first long name is in a pax extended header
+    // and the second is split into the ustar prefix and name
+    const PAX_ARCHIVE: &[u8] = include_bytes!("../fixtures/bundle-pax.tar");
+
+    fn entries(archive: &[u8]) -> Vec<(String, EntryKind, String)> {
+        let mut archive = TarArchive::new(archive);
+        let mut entries = Vec::new();
+
+        while let Some(entry) = archive.next_entry().unwrap() {
+            let mut text = String::new();
+            archive.entry_data().read_to_string(&mut text).unwrap();
+            entries.push((entry.path, entry.kind, text));
+        }
+
+        entries
+    }
+
+    fn long_name() -> String {
+        format!("var/log/{}/service.log", "nested".repeat(20))
+    }
+
+    fn deep_name() -> String {
+        format!("{}notes.txt", "deep/".repeat(25))
+    }
+
+    #[test]
+    fn tar_archive_reads_members_with_gnu_long_names() {
+        let entries = entries(GNU_ARCHIVE);
+
+        assert_eq!(
+            vec![
+                (String::from("var/log/app.log"), EntryKind::File, String::from("started\nERROR: disk full\n")),
+                (String::from("var/log/empty/"), EntryKind::Directory, String::new()),
+                (long_name(), EntryKind::File, String::from("ERROR: in a long name\n")),
+                (String::from("var/log/latest"), EntryKind::Other, String::new()),
+            ],
+            entries[..4]
+        );
+        assert_eq!((deep_name(), String::from("no errors here\n")), (entries[4].0.clone(), entries[4].2.clone()));
+        assert_eq!(5, entries.len());
+    }
+
+    #[test]
+    fn tar_archive_reads_pax_paths_and_ustar_prefix() {
+        let paths: Vec<String> = entries(PAX_ARCHIVE).into_iter().map(|(path, _, _)| path).collect();
+
+        assert_eq!(vec![String::from("var/log/app.log"), String::from("var/log/empty/")], paths[..2]);
+        assert_eq!(long_name(), paths[2]);
+        assert_eq!(deep_name(), paths[4]);
+        assert_eq!(5, paths.len());
+    }
+
+    // A ustar header block for a member with this name, type and size, with its checksum filled in
+    fn header_block(name: &str, type_flag: u8, size: usize) -> Vec<u8> {
+        let mut header = vec![0; BLOCK_SIZE as usize];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[SIZE][..11].copy_from_slice(format!("{size:011o}").as_bytes());
+        header[TYPE_FLAG] = type_flag;
+        header[MAGIC].copy_from_slice(b"ustar\0");
+
+        header[CHECKSUM].fill(b' ');
+        let sum: u32 = header.iter().map(|&byte| u32::from(byte)).sum();
+        header[CHECKSUM][..7].copy_from_slice(format!("{sum:06o}\0").as_bytes());
+        header
+    }
+
+    fn padded(mut data: Vec<u8>) -> Vec<u8> {
+        data.resize(data.len().next_multiple_of(BLOCK_SIZE as usize), 0);
+        data
+    }
+
+    #[test]
+    fn tar_archive_reads_path_from_pax_header_with_big_records() {
+        // An extended attribute far bigger than any name, before and after the path
+        let xattr_value = "x".repeat(100_000);
+        let xattr = format!("SCHILY.xattr.user.blob={xattr_value}\n");
+        let xattr = format!("{} {xattr}", xattr.len() + 7);
+        let path = "9 path=a\n";
+        let records = format!("{xattr}{path}{xattr}");
+
+        let mut archive = header_block("PaxHeader", b'x', records.len());
+        archive.extend(padded(records.into_bytes()));
+        archive.extend(header_block("short", b'0', 6));
+        archive.extend(padded(b"found\n".to_vec()));
+        archive.extend([0; 1024]);
+
+        let entries = entries(&archive);
+        assert_eq!(vec![(String::from("a"), EntryKind::File, String::from("found\n"))], entries);
+
+        // A GNU long name that big is still not believed
+        let mut archive = header_block("././@LongLink", b'L', 100_000);
+        archive.extend(padded(vec![b'a'; 100_000]));
+        assert_eq!(io::ErrorKind::InvalidData, TarArchive::new(&archive[..]).next_entry().unwrap_err().kind());
+    }
+
+    #[test]
+    fn tar_archive_skips_data_that_is_not_read() {
+        let mut archive = TarArchive::new(GNU_ARCHIVE);
+        let mut paths = Vec::new();
+        while let Some(entry) = archive.next_entry().unwrap() {
+            paths.push(entry.path);
+        }
+
+        assert_eq!(5, paths.len());
+    }
+
+    #[test]
+    fn tar_archive_fails_on_broken_archive() {
+        let mut corrupt = GNU_ARCHIVE.to_vec();
+        corrupt[0] ^= 0xFF;
+        assert_eq!(io::ErrorKind::InvalidData, TarArchive::new(&corrupt[..]).next_entry().unwrap_err().kind());
+
+        let mut cut_off = TarArchive::new(&GNU_ARCHIVE[..600]);
+        cut_off.next_entry().unwrap();
+        assert_eq!(io::ErrorKind::UnexpectedEof, cut_off.next_entry().unwrap_err().kind());
+    }
+
+    #[test]
+    fn is_tar_checks_magic_and_checksum() {
+        assert!(is_tar(GNU_ARCHIVE));
+        assert!(is_tar(PAX_ARCHIVE));
+        assert!(!is_tar(&GNU_ARCHIVE[..511]));
+        assert!(!is_tar(&[0; 1024]));
+    }
+
+    #[test]
+    fn parse_size_reads_octal_and_binary_sizes() {
+        assert_eq!(1234, parse_size(b"00000002322\0").unwrap());
+        assert_eq!(1 << 33, parse_size(b"\x80\0\0\0\0\0\0\x02\0\0\0\0").unwrap());
+        assert!(parse_size(b"12x\0").is_err());
+    }
+}
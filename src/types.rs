@@ -0,0 +1,134 @@
+use crate::error::MinigrepError;
+
+// The file types that -t and -T select by name, like `-t rust`. A type is a list of globs,
+// which are file extensions or well known file names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileTypes {
+    // Kept sorted by name, which is the order --type-list prints them in
+    types: Vec<(String, Vec<String>)>,
+}
+
+const DEFAULT_TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    ("cmake", &["CMakeLists.txt", "*.cmake"]),
+    ("cpp", &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx"]),
+    ("csharp", &["*.cs"]),
+    ("css", &["*.css", "*.scss", "*.sass", "*.less"]),
+    ("docker", &["Dockerfile", "*.dockerfile"]),
+    ("go", &["*.go", "go.mod", "go.sum"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.mjs", "*.cjs", "*.jsx"]),
+    ("json", &["*.json"]),
+    ("kotlin", &["*.kt", "*.kts"]),
+    ("make", &["Makefile", "makefile", "GNUmakefile", "*.mk"]),
+    ("markdown", &["*.md", "*.markdown"]),
+    ("python", &["*.py", "*.pyi"]),
+    ("ruby", &["*.rb", "Gemfile", "Rakefile"]),
+    ("rust", &["*.rs", "Cargo.toml"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("sql", &["*.sql"]),
+    ("swift", &["*.swift"]),
+    ("toml", &["*.toml"]),
+    ("ts", &["*.ts", "*.mts", "*.cts", "*.tsx"]),
+    ("txt", &["*.txt"]),
+    ("xml", &["*.xml"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+impl FileTypes {
+    pub fn new() -> Self {
+        let types = DEFAULT_TYPES
+            .iter()
+            .map(|(name, globs)| (String::from(*name), globs.iter().map(|glob| String::from(*glob)).collect()))
+            .collect();
+
+        Self { types }
+    }
+
+    // Adds a type from a definition like `proto:*.proto`, or several globs like `web:*.html,*.css`.
+    // Adding to a type that already exists adds the globs to the ones it has.
+    pub fn add(&mut self, definition: &str) -> Result<(), MinigrepError> {
+        let bad_definition = || MinigrepError::BadFlag {
+            flag: String::from("--type-add"),
+            message: format!("needs a definition like `name:*.ext`, not `{definition}`"),
+        };
+
+        let (name, globs) = definition.split_once(':').ok_or_else(bad_definition)?;
+        let globs: Vec<String> = globs
+            .split(',')
+            .filter(|glob| !glob.is_empty())
+            .map(String::from)
+            .collect();
+        if name.is_empty() || globs.is_empty() {
+            return Err(bad_definition());
+        }
+
+        match self.types.binary_search_by(|(existing, _)| existing.as_str().cmp(name)) {
+            Ok(index) => self.types[index].1.extend(globs),
+            Err(index) => self.types.insert(index, (String::from(name), globs)),
+        }
+
+        Ok(())
+    }
+
+    pub fn globs(&self, name: &str) -> Option<&[String]> {
+        self.types
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, globs)| globs.as_slice())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.types.iter().map(|(name, globs)| (name.as_str(), globs.as_slice()))
+    }
+}
+
+impl Default for FileTypes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_types_have_built_in_definitions() {
+        let types = FileTypes::new();
+
+        assert_eq!(Some(&[String::from("*.rs"), String::from("Cargo.toml")][..]), types.globs("rust"));
+        assert_eq!(None, types.globs("proto"));
+    }
+
+    #[test]
+    fn file_types_add_new_types_in_sorted_order() {
+        let mut types = FileTypes::new();
+        types.add("proto:*.proto").unwrap();
+
+        assert_eq!(Some(&[String::from("*.proto")][..]), types.globs("proto"));
+        let names: Vec<&str> = types.iter().map(|(name, _)| name).collect();
+        assert!(names.is_sorted());
+    }
+
+    #[test]
+    fn file_types_add_globs_to_existing_type() {
+        let mut types = FileTypes::new();
+        types.add("txt:*.log,*.out").unwrap();
+
+        assert_eq!(
+            Some(&[String::from("*.txt"), String::from("*.log"), String::from("*.out")][..]),
+            types.globs("txt")
+        );
+    }
+
+    #[test]
+    fn file_types_fail_on_bad_definition() {
+        let mut types = FileTypes::new();
+
+        assert!(types.add("proto").is_err());
+        assert!(types.add(":*.proto").is_err());
+        assert!(types.add("proto:").is_err());
+    }
+}
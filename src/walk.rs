@@ -0,0 +1,384 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::filter::PathFilter;
+use crate::ignore::Gitignore;
+
+// Walks the paths given on the command line and yields every file to search.
+// Directories are only entered when searching recursively, and their entries are visited
+// in sorted order so the output is the same every time.
+//
+// Inside the directories, hidden files and directories (the ones starting with a dot) are skipped,
+// and so is everything that the .ignore and .gitignore files in the directory (or above it, up to the
+// root of the git repository) or .git/info/exclude ignore, and what the PathFilter leaves out.
+// Paths given on the command line are always searched.
+pub struct Walk {
+    // Paths left to visit. The next one is at the end, so the stack is kept in reverse order.
+    stack: Vec<Pending>,
+    recursive: bool,
+    use_ignore_files: bool,
+    hidden: bool,
+    filter: PathFilter,
+}
+
+struct Pending {
+    path: PathBuf,
+    // The path given to the walk that this one was found in, which the filter matches from
+    root: Arc<Path>,
+    // The ignore rules of the directory the path is in, or none for a path given to the walk
+    frame: Option<Arc<IgnoreFrame>>,
+}
+
+// The ignore rules of one directory, linked to the ones of the directory it is in
+struct IgnoreFrame {
+    // The directory that the paths checked against this frame are under
+    dir: PathBuf,
+    // For a directory above the walk, the path from it down to dir, which the rules see in front of
+    // every path
+    prefix: PathBuf,
+    // .ignore comes first, since it wins over .gitignore, which wins over .git/info/exclude
+    ignores: Vec<Gitignore>,
+    parent: Option<Arc<IgnoreFrame>>,
+}
+
+impl IgnoreFrame {
+    fn load(dir: &Path, prefix: PathBuf, parent: Option<Arc<IgnoreFrame>>, ignore_dir: &Path) -> io::Result<Self> {
+        let mut ignores = vec![
+            Gitignore::from_file(&ignore_dir.join(".ignore"))?,
+            Gitignore::from_file(&ignore_dir.join(".gitignore"))?,
+        ];
+        if ignore_dir.join(".git").is_dir() {
+            ignores.push(Gitignore::from_file(&ignore_dir.join(".git").join("info").join("exclude"))?);
+        }
+        ignores.retain(|ignore| !ignore.is_empty());
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            prefix,
+            ignores,
+            parent,
+        })
+    }
+
+    // The rules of a directory win over the ones of the directories above it
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut frame = Some(self);
+
+        while let Some(current) = frame {
+            if let Ok(relative_path) = path.strip_prefix(&current.dir) {
+                let relative_path = current.prefix.join(relative_path);
+                for ignore in &current.ignores {
+                    if let Some(ignored) = ignore.matched(&relative_path, is_dir) {
+                        return ignored;
+                    }
+                }
+            }
+            frame = current.parent.as_deref();
+        }
+
+        false
+    }
+}
+
+#[derive(Debug)]
+pub struct WalkError {
+    pub path: PathBuf,
+    pub error: io::Error,
+}
+
+impl fmt::Display for WalkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+impl Walk {
+    pub fn new<P: AsRef<Path>>(paths: &[P], recursive: bool) -> Self {
+        let stack = paths
+            .iter()
+            .rev()
+            .map(|path| Pending {
+                path: path.as_ref().to_path_buf(),
+                root: Arc::from(path.as_ref()),
+                frame: None,
+            })
+            .collect();
+
+        Self {
+            stack,
+            recursive,
+            use_ignore_files: true,
+            hidden: false,
+            filter: PathFilter::new(),
+        }
+    }
+
+    // Leaves out the files (and directories) that the --glob, -t and -T filter does not allow
+    pub fn with_filter(mut self, filter: PathFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    // With false (--no-ignore), nothing is skipped because of an ignore file
+    pub fn with_ignore_files(mut self, use_ignore_files: bool) -> Self {
+        self.use_ignore_files = use_ignore_files;
+        self
+    }
+
+    // With true (--hidden), hidden files and directories are searched too
+    pub fn with_hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    // A directory given on the command line also gets the rules of the directories above it,
+    // as far up as the root of the git repository it is in. Outside a repository there are none.
+    fn parent_frames(&self, dir: &Path) -> io::Result<Option<Arc<IgnoreFrame>>> {
+        let absolute_dir = fs::canonicalize(dir)?;
+        let Some(root) = absolute_dir.ancestors().skip(1).find(|ancestor| ancestor.join(".git").exists()) else {
+            return Ok(None);
+        };
+
+        // The ancestors come from the inside out, so the outermost one is linked up first
+        let ancestors: Vec<&Path> = absolute_dir
+            .ancestors()
+            .skip(1)
+            .take_while(|ancestor| ancestor.starts_with(root))
+            .collect();
+
+        let mut parent = None;
+        for ancestor in ancestors.into_iter().rev() {
+            let prefix = absolute_dir.strip_prefix(ancestor).unwrap_or(Path::new("")).to_path_buf();
+            let frame = IgnoreFrame::load(dir, prefix, parent, ancestor)?;
+            parent = Some(Arc::new(frame));
+        }
+
+        Ok(parent)
+    }
+
+    fn push_dir_entries(&mut self, dir: &Path, root: Arc<Path>, frame: Option<Arc<IgnoreFrame>>) -> io::Result<()> {
+        let frame = match (self.use_ignore_files, frame) {
+            (false, _) => None,
+            (true, Some(parent)) => Some(Arc::new(IgnoreFrame::load(dir, PathBuf::new(), Some(parent), dir)?)),
+            // A directory given on the command line
+            (true, None) => {
+                let parent = self.parent_frames(dir)?;
+                Some(Arc::new(IgnoreFrame::load(dir, PathBuf::new(), parent, dir)?))
+            }
+        };
+
+        let mut entries: Vec<PathBuf> = Vec::new();
+
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+
+            // Like grep -r, symbolic links found inside a directory are not followed,
+            // which also keeps links that point back up the tree from looping forever
+            if file_type.is_symlink() {
+                continue;
+            }
+            if !self.hidden && entry.file_name().as_encoded_bytes().starts_with(b".") {
+                continue;
+            }
+
+            let path = entry.path();
+            if frame.as_ref().is_some_and(|frame| frame.is_ignored(&path, file_type.is_dir())) {
+                continue;
+            }
+            if !self.filter.is_empty() {
+                let relative_path = path.strip_prefix(&root).unwrap_or(&path);
+                if !self.filter.allows(relative_path, file_type.is_dir()) {
+                    continue;
+                }
+            }
+            entries.push(path);
+        }
+
+        entries.sort();
+        self.stack.extend(entries.into_iter().rev().map(|path| Pending {
+            path,
+            root: root.clone(),
+            frame: frame.clone(),
+        }));
+
+        Ok(())
+    }
+}
+
+impl Iterator for Walk {
+    type Item = Result<PathBuf, WalkError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Pending { path, root, frame } = self.stack.pop()?;
+
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(error) => return Some(Err(WalkError { path, error })),
+            };
+
+            if !metadata.is_dir() {
+                return Some(Ok(path));
+            }
+
+            if !self.recursive {
+                let error = io::Error::other("Is a directory (use -r to search it)");
+                return Some(Err(WalkError { path, error }));
+            }
+
+            if let Err(error) = self.push_dir_entries(&path, root, frame) {
+                return Some(Err(WalkError { path, error }));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    // Creates an empty directory in the system temp dir that is unique to the test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("minigrep-walk-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn walk_yields_files_in_given_order() {
+        let dir = temp_dir("order");
+        fs::write(dir.join("b.txt"), "b").unwrap();
+        fs::write(dir.join("a.txt"), "a").unwrap();
+
+        let paths = [dir.join("b.txt"), dir.join("a.txt")];
+        let files: Vec<PathBuf> = Walk::new(&paths, false).map(Result::unwrap).collect();
+
+        assert_eq!(paths.to_vec(), files);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn walk_enters_directories_in_sorted_order_when_recursive() {
+        let dir = temp_dir("recursive");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub").join("c.txt"), "c").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+        fs::write(dir.join("a.txt"), "a").unwrap();
+
+        let files: Vec<PathBuf> = Walk::new(&[&dir], true).map(Result::unwrap).collect();
+
+        assert_eq!(
+            vec![dir.join("a.txt"), dir.join("b.txt"), dir.join("sub").join("c.txt")],
+            files
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn walk_reports_directories_when_not_recursive() {
+        let dir = temp_dir("not-recursive");
+
+        let results: Vec<Result<PathBuf, WalkError>> = Walk::new(&[&dir], false).collect();
+
+        assert_eq!(1, results.len());
+        assert!(results[0].is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn walk_reports_missing_paths_and_keeps_going() {
+        let dir = temp_dir("missing");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+
+        let paths = [dir.join("missing.txt"), dir.join("a.txt")];
+        let results: Vec<Result<PathBuf, WalkError>> = Walk::new(&paths, false).collect();
+
+        assert_eq!(dir.join("missing.txt"), results[0].as_ref().unwrap_err().path);
+        assert_eq!(&dir.join("a.txt"), results[1].as_ref().unwrap());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    // Walks the directory and returns the files found, relative to it
+    fn walk_files(walk: Walk, dir: &Path) -> Vec<String> {
+        walk.map(Result::unwrap)
+            .map(|path| path.strip_prefix(dir).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    fn write_files(dir: &Path, files: &[(&str, &str)]) {
+        for (name, text) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+    }
+
+    #[test]
+    fn walk_skips_ignored_and_hidden_files() {
+        let dir = temp_dir("ignore");
+        write_files(&dir, &[
+            (".gitignore", "target/\n*.log\n!keep.log\n"),
+            (".hidden", "h"),
+            ("app.log", "a"),
+            ("keep.log", "k"),
+            ("main.rs", "m"),
+            ("target/debug.rs", "d"),
+            ("web/.ignore", "dist\n"),
+            ("web/dist/bundle.js", "b"),
+            ("web/index.js", "i"),
+        ]);
+
+        assert_eq!(vec!["keep.log", "main.rs", "web/index.js"], walk_files(Walk::new(&[&dir], true), &dir));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn walk_lets_nested_ignore_files_override_outer_ones() {
+        let dir = temp_dir("nested-ignore");
+        write_files(&dir, &[
+            (".gitignore", "*.txt\n"),
+            ("a.txt", "a"),
+            ("docs/.gitignore", "!*.txt\n"),
+            ("docs/b.txt", "b"),
+        ]);
+
+        assert_eq!(vec!["docs/b.txt"], walk_files(Walk::new(&[&dir], true), &dir));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn walk_uses_git_info_exclude_and_rules_above_the_walk() {
+        let dir = temp_dir("git-exclude");
+        write_files(&dir, &[
+            (".git/info/exclude", "/src/generated.rs\n"),
+            (".gitignore", "*.bak\n"),
+            ("src/generated.rs", "g"),
+            ("src/lib.rs", "l"),
+            ("src/lib.rs.bak", "b"),
+        ]);
+
+        let src = dir.join("src");
+        assert_eq!(vec!["lib.rs"], walk_files(Walk::new(&[&src], true), &src));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn walk_searches_everything_with_no_ignore_and_hidden() {
+        let dir = temp_dir("no-ignore");
+        write_files(&dir, &[(".gitignore", "*.log\n"), ("app.log", "a"), ("main.rs", "m")]);
+
+        let walk = Walk::new(&[&dir], true).with_ignore_files(false).with_hidden(true);
+        assert_eq!(vec![".gitignore", "app.log", "main.rs"], walk_files(walk, &dir));
+
+        // A file given by its path is searched even when it is ignored
+        let log = dir.join("app.log");
+        assert_eq!(vec![log.clone()], Walk::new(&[&log], true).map(Result::unwrap).collect::<Vec<PathBuf>>());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}